@@ -24,3 +24,194 @@ extend {
     is_meshable = true,
     color = {1, 1, 1}
 }
+
+extend {
+    type = "block",
+    name = "stone",
+    order = "a[blocks]-d[stone]",
+    is_transparent = false,
+    is_meshable = true,
+    color = {0.5, 0.5, 0.5}
+}
+
+extend {
+    type = "block",
+    name = "sand",
+    order = "a[blocks]-e[sand]",
+    is_transparent = false,
+    is_meshable = true,
+    color = {0.86, 0.8, 0.55}
+}
+
+extend {
+    type = "block",
+    name = "snow",
+    order = "a[blocks]-f[snow]",
+    is_transparent = false,
+    is_meshable = true,
+    color = {0.95, 0.97, 1}
+}
+
+extend {
+    type = "biome",
+    name = "plains",
+    temperature = 0,
+    humidity = 0,
+    surface_block = "grass",
+    subsurface_block = "dirt",
+    filler_block = "stone",
+    height = 200,
+    height_variation = 30,
+    overhang = 55
+}
+
+extend {
+    type = "biome",
+    name = "desert",
+    temperature = 0.8,
+    humidity = -0.8,
+    surface_block = "sand",
+    subsurface_block = "sand",
+    subsurface_depth = 6,
+    filler_block = "stone",
+    height = 195,
+    height_variation = 12,
+    overhang = 10
+}
+
+extend {
+    type = "biome",
+    name = "mountains",
+    temperature = -0.7,
+    humidity = 0.2,
+    surface_block = "snow",
+    subsurface_block = "stone",
+    filler_block = "stone",
+    height = 240,
+    height_variation = 80,
+    overhang = 70
+}
+
+extend {
+    type = "block",
+    name = "iron-ore",
+    order = "b[ores]-a[iron-ore]",
+    is_transparent = false,
+    is_meshable = true,
+    color = {0.55, 0.45, 0.4}
+}
+
+extend {
+    type = "block",
+    name = "copper-ore",
+    order = "b[ores]-b[copper-ore]",
+    is_transparent = false,
+    is_meshable = true,
+    color = {0.8, 0.45, 0.25}
+}
+
+extend {
+    type = "resource",
+    name = "iron-ore",
+    block = "iron-ore",
+    autoplace = {
+        frequency = 1.2,
+        size = 1,
+        richness = 1,
+        min_height = -160,
+        max_height = 190,
+        distance_scaling = 1
+    }
+}
+
+extend {
+    type = "resource",
+    name = "copper-ore",
+    block = "copper-ore",
+    autoplace = {
+        frequency = 1,
+        size = 0.9,
+        richness = 1,
+        min_height = -160,
+        max_height = 180,
+        distance_scaling = 1
+    }
+}
+
+extend {
+    type = "block",
+    name = "log",
+    order = "c[plants]-a[log]",
+    is_transparent = false,
+    is_meshable = true,
+    color = {0.45, 0.3, 0.15}
+}
+
+extend {
+    type = "block",
+    name = "leaves",
+    order = "c[plants]-b[leaves]",
+    is_transparent = false,
+    is_meshable = true,
+    color = {0.2, 0.55, 0.15}
+}
+
+local function tree_blocks(height)
+    local blocks = {}
+    for y = 0, height - 1 do
+        table.insert(blocks, {position = {0, y, 0}, block = "log"})
+    end
+    for x = -2, 2 do
+        for y = height - 2, height + 1 do
+            for z = -2, 2 do
+                local is_trunk = x == 0 and z == 0 and y < height
+                local spread = math.abs(x) + math.abs(z) + math.max(0, y - height + 1)
+                if not is_trunk and spread <= 3 then
+                    table.insert(blocks, {position = {x, y, z}, block = "leaves"})
+                end
+            end
+        end
+    end
+    return blocks
+end
+
+local function boulder_blocks(radius)
+    local blocks = {}
+    for x = -radius, radius do
+        for y = -radius, radius do
+            for z = -radius, radius do
+                if x * x + y * y + z * z <= radius * radius then
+                    table.insert(blocks, {position = {x, y, z}, block = "stone"})
+                end
+            end
+        end
+    end
+    return blocks
+end
+
+extend {
+    type = "feature",
+    name = "tree",
+    frequency = 6,
+    biomes = {"plains"},
+    place_on = {"grass"},
+    blocks = tree_blocks(5)
+}
+
+extend {
+    type = "feature",
+    name = "boulder",
+    frequency = 0.5,
+    place_on = {"grass", "sand", "snow"},
+    blocks = boulder_blocks(2)
+}
+
+extend {
+    type = "flat-preset",
+    name = "default",
+    layers = {
+        {block = "stone", thickness = 60},
+        {block = "dirt", thickness = 3},
+        {block = "grass", thickness = 1}
+    }
+}
@@ -0,0 +1,350 @@
+//! Renders a top-down map of a world without opening a window, for tuning world generation.
+//!
+//! ```text
+//! cargo run --bin worldgen_preview -- --seed 1234 --size 1024 --shading --overlay biomes
+//! ```
+//!
+//! Every pixel is one block column, coloured by the block on top. Features such as trees aren't placed.
+//! Run with `--help` for every option.
+
+use std::{
+    path::PathBuf,
+    sync::{
+        Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    thread,
+};
+
+use anyhow::{Context, Result, anyhow, bail};
+use bevy::{
+    color::{ColorToPacked, Mix},
+    prelude::*,
+};
+use image::{Rgb, RgbImage};
+
+use talc::{
+    chunky::chunk::{CHUNK_SIZE, CHUNK_SIZE_I32, CHUNK_SIZE2, set_block_registry},
+    mod_manager::{
+        mod_loader::load_prototypes,
+        prototypes::{BlockPrototype, Prototypes},
+    },
+    position::{ChunkPosition, Position},
+    worldgen::{
+        caves::CaveSettings,
+        generator::{GeneratorContext, WorldGenerator, WorldGenerators},
+        noise_generator::NoiseWorldGenerator,
+        world_info::{WorldInfo, WorldSeed, name_salt},
+    },
+};
+
+const HELP: &str = "\
+Renders a top-down map of a world to a PNG.
+
+Options:
+    --world DIR         use the generator, seed and settings of an existing world
+    --generator NAME    the generator to use when there is no --world (default: noise)
+    --seed SEED         the seed to use when there is no --world (default: 0)
+    --x X, --z Z        the center of the map, in blocks (default: 0)
+    --size BLOCKS       the width and height of the map, in blocks, at most 8192 (default: 512)
+    --top Y             the highest block looked at (default: 384)
+    --bottom Y          the lowest block looked at (default: -160)
+    --shading           darken low and west facing ground
+    --overlay KIND      tint the map by `biomes`, or draw the highest ore of each column for `resources`
+    --out FILE          where to write the map (default: worldgen_preview.png)";
+
+/// The widest map that is rendered. Every column is kept in memory until the map is written.
+const MAX_SIZE: u32 = 8192;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Overlay {
+    Biomes,
+    Resources,
+}
+
+struct Options {
+    world: Option<PathBuf>,
+    generator: String,
+    seed: u64,
+    center: IVec2,
+    size: u32,
+    top: i32,
+    bottom: i32,
+    shading: bool,
+    overlay: Option<Overlay>,
+    out: PathBuf,
+}
+
+impl Options {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Option<Self>> {
+        let mut options = Self {
+            world: None,
+            generator: NoiseWorldGenerator::NAME.to_string(),
+            seed: 0,
+            center: IVec2::ZERO,
+            size: 512,
+            top: 384,
+            bottom: -160,
+            shading: false,
+            overlay: None,
+            out: PathBuf::from("worldgen_preview.png"),
+        };
+
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| anyhow!("Expected a value after {arg}."))
+            };
+            match arg.as_str() {
+                "--help" | "-h" => return Ok(None),
+                "--world" => options.world = Some(PathBuf::from(value()?)),
+                "--generator" => options.generator = value()?,
+                "--seed" => options.seed = value()?.parse().context("Could not parse --seed")?,
+                "--x" => options.center.x = value()?.parse().context("Could not parse --x")?,
+                "--z" => options.center.y = value()?.parse().context("Could not parse --z")?,
+                "--size" => options.size = value()?.parse().context("Could not parse --size")?,
+                "--top" => options.top = value()?.parse().context("Could not parse --top")?,
+                "--bottom" => {
+                    options.bottom = value()?.parse().context("Could not parse --bottom")?;
+                }
+                "--shading" => options.shading = true,
+                "--overlay" => {
+                    options.overlay = Some(match value()?.as_str() {
+                        "biomes" => Overlay::Biomes,
+                        "resources" => Overlay::Resources,
+                        other => bail!("Unknown overlay `{other}`. Expected biomes or resources."),
+                    });
+                }
+                "--out" => options.out = PathBuf::from(value()?),
+                other => bail!("Unknown option `{other}`.\n\n{HELP}"),
+            }
+        }
+
+        if options.size == 0 || options.bottom > options.top {
+            bail!("The map needs a size above 0, and --bottom can't be above --top.");
+        }
+        if options.size > MAX_SIZE {
+            bail!(
+                "The map can be at most {MAX_SIZE} blocks wide, got --size {}.",
+                options.size
+            );
+        }
+        Ok(Some(options))
+    }
+}
+
+/// What was found in one block column.
+#[derive(Clone, Copy, Default)]
+struct Column {
+    /// the highest solid block and its y
+    surface: Option<(i32, &'static BlockPrototype)>,
+    /// the highest ore block
+    ore: Option<&'static BlockPrototype>,
+}
+
+fn main() -> Result<()> {
+    let Some(options) = Options::parse(std::env::args().skip(1))? else {
+        println!("{HELP}");
+        return Ok(());
+    };
+
+    let prototypes = load_prototypes().context("Failed to load mods")?;
+    set_block_registry(&prototypes.blocks);
+
+    let world_info = match &options.world {
+        Some(directory) => WorldInfo::load(directory)?,
+        None => WorldInfo {
+            generator: options.generator.clone(),
+            seed: WorldSeed(options.seed),
+            caves: CaveSettings::default(),
+            heightmap: None,
+            flat: None,
+        },
+    };
+    let mut generators = WorldGenerators::default();
+    generators.register_builtin();
+    let generator = generators.create(
+        &world_info.generator,
+        &GeneratorContext {
+            block_prototypes: &prototypes.blocks,
+            biome_prototypes: &prototypes.biomes,
+            resource_prototypes: &prototypes.resources,
+            feature_prototypes: &prototypes.features,
+            flat_preset_prototypes: &prototypes.flat_presets,
+            caves: world_info.caves,
+            heightmap: world_info.heightmap.as_ref(),
+            flat: world_info.flat.as_ref(),
+            seed: world_info.seed,
+        },
+    )?;
+    let ores: Vec<&'static BlockPrototype> = prototypes
+        .resources
+        .iter()
+        .filter_map(|(_, resource)| prototypes.blocks.get(&resource.block))
+        .collect();
+
+    let min = options.center - IVec2::splat(options.size as i32 / 2);
+    let max = min + IVec2::splat(options.size as i32);
+    let min_chunk = min.div_euclid(IVec2::splat(CHUNK_SIZE_I32));
+    let max_chunk = (max - IVec2::ONE).div_euclid(IVec2::splat(CHUNK_SIZE_I32));
+    let chunk_columns: Vec<IVec2> = (min_chunk.y..=max_chunk.y)
+        .flat_map(|z| (min_chunk.x..=max_chunk.x).map(move |x| IVec2::new(x, z)))
+        .collect();
+
+    // chunk columns are handed out to every core in turn
+    let next = AtomicUsize::new(0);
+    let scanned = Mutex::new(vec![]);
+    let threads = thread::available_parallelism().map_or(1, usize::from);
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| {
+                loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(&chunk_column) = chunk_columns.get(i) else {
+                        break;
+                    };
+                    let columns = scan_chunk_column(&*generator, chunk_column, &options, &ores);
+                    scanned
+                        .lock()
+                        .expect("A scanning thread panicked")
+                        .push((chunk_column, columns));
+                }
+            });
+        }
+    });
+
+    let mut columns = vec![Column::default(); (options.size * options.size) as usize];
+    for (chunk_column, chunk_columns) in scanned.into_inner().expect("A scanning thread panicked") {
+        for (i, column) in chunk_columns.into_iter().enumerate() {
+            let position = chunk_column * CHUNK_SIZE_I32
+                + IVec2::new((i % CHUNK_SIZE) as i32, (i / CHUNK_SIZE) as i32)
+                - min;
+            if position.cmpge(IVec2::ZERO).all() && position.cmplt(max - min).all() {
+                columns[position.x as usize + position.y as usize * options.size as usize] = column;
+            }
+        }
+    }
+
+    let image = draw(&columns, &*generator, min, &options);
+    image
+        .save(&options.out)
+        .with_context(|| format!("Could not write {}", options.out.display()))?;
+    println!(
+        "Wrote a {0}x{0} map of {1} around {2} to {3}",
+        options.size,
+        world_info.generator,
+        options.center,
+        options.out.display()
+    );
+    Ok(())
+}
+
+/// Generates a column of chunks from the top down, until the surface of every block column is found.
+/// With the resources overlay it keeps going down to `bottom` to find ores.
+fn scan_chunk_column(
+    generator: &dyn WorldGenerator,
+    chunk_column: IVec2,
+    options: &Options,
+    ores: &[&'static BlockPrototype],
+) -> Vec<Column> {
+    let mut columns = vec![Column::default(); CHUNK_SIZE2];
+    let needs_ores = options.overlay == Some(Overlay::Resources);
+    let top_chunk = options.top.div_euclid(CHUNK_SIZE_I32);
+    let bottom_chunk = options.bottom.div_euclid(CHUNK_SIZE_I32);
+
+    for chunk_y in (bottom_chunk..=top_chunk).rev() {
+        let chunk_position = ChunkPosition::new(chunk_column.x, chunk_y, chunk_column.y);
+        let chunk = generator.generate(chunk_position);
+        let min_y = Position::from(chunk_position).y;
+
+        for (i, column) in columns.iter_mut().enumerate() {
+            let (x, z) = ((i % CHUNK_SIZE) as i32, (i / CHUNK_SIZE) as i32);
+            for y in (0..CHUNK_SIZE_I32).rev() {
+                let world_y = min_y + y;
+                if !(options.bottom..=options.top).contains(&world_y) {
+                    continue;
+                }
+                let block = chunk.get_block(Position::new(x, y, z).into());
+                if column.surface.is_none() && block.is_meshable {
+                    column.surface = Some((world_y, block));
+                }
+                if column.ore.is_none() && ores.contains(&block) {
+                    column.ore = Some(block);
+                }
+                if column.surface.is_some() && (!needs_ores || column.ore.is_some()) {
+                    break;
+                }
+                // the rest of a homogeneous chunk is the same block
+                if chunk.is_homogenous() && column.surface.is_some() {
+                    break;
+                }
+            }
+        }
+
+        let done = columns
+            .iter()
+            .all(|column| column.surface.is_some() && (!needs_ores || column.ore.is_some()));
+        if done {
+            break;
+        }
+    }
+    columns
+}
+
+fn draw(
+    columns: &[Column],
+    generator: &dyn WorldGenerator,
+    min: IVec2,
+    options: &Options,
+) -> RgbImage {
+    let size = options.size;
+    let heights = columns
+        .iter()
+        .filter_map(|column| column.surface.map(|(y, _)| y));
+    let lowest = heights.clone().min().unwrap_or(0);
+    let highest = heights.max().unwrap_or(0);
+    let height_at = |x: u32, z: u32| columns[(x + z * size) as usize].surface.map(|(y, _)| y);
+
+    RgbImage::from_fn(size, size, |x, z| {
+        let column = columns[(x + z * size) as usize];
+        let Some((height, block)) = column.surface else {
+            return Rgb([0, 0, 0]);
+        };
+        let mut colour = block.color.to_linear();
+
+        if options.shading {
+            let relative = (height - lowest) as f32 / (highest - lowest).max(1) as f32;
+            // light comes from the west
+            let slope = x
+                .checked_sub(1)
+                .and_then(|west| height_at(west, z))
+                .map_or(0, |west| height - west);
+            let shade = (slope as f32)
+                .mul_add(0.08, relative.mul_add(0.5, 0.6))
+                .clamp(0.3, 1.3);
+            colour = colour * shade;
+        }
+        match options.overlay {
+            Some(Overlay::Biomes) => {
+                if let Some(biome) = generator.biome_at(min.x + x as i32, min.y + z as i32) {
+                    colour = colour.mix(&biome_colour(&biome.name), 0.5);
+                }
+            }
+            Some(Overlay::Resources) => {
+                if let Some(ore) = column.ore {
+                    colour = ore.color.to_linear();
+                }
+            }
+            None => {}
+        }
+
+        let [red, green, blue, _] = Color::from(colour).to_srgba().to_u8_array();
+        Rgb([red, green, blue])
+    })
+}
+
+/// A bright colour that is always the same for a biome.
+fn biome_colour(name: &str) -> LinearRgba {
+    let hue = (name_salt(name) % 360) as f32;
+    Color::hsl(hue, 0.8, 0.5).to_linear()
+}
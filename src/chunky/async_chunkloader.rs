@@ -1,14 +1,22 @@
-use std::{sync::Arc, vec::Drain};
+use std::{
+    collections::VecDeque,
+    sync::Arc,
+    time::Instant,
+};
 
+use anyhow::{Result, anyhow, ensure};
 use bevy::{
+    diagnostic::Diagnostics,
+    math::I64Vec3,
     platform::collections::{HashMap, HashSet},
     prelude::*,
-    render::primitives::Aabb,
-    tasks::{block_on, AsyncComputeTaskPool, Task},
+    render::primitives::{Aabb, Frustum},
 };
 
-use crate::mod_manager::prototypes::BlockPrototypes;
-use crate::position::{ChunkPosition, FloatingPosition};
+use crate::console::commands::{AddConsoleCommand, ConsoleCommand, parse_arg};
+use crate::mod_manager::prototypes::{BlockPrototypes, Prototypes};
+use crate::position::{ChunkPosition, FloatingPosition, Position};
+use crate::worldgen::generator::ActiveWorldGenerator;
 use crate::{
     chunky::{
         chunk::{
@@ -18,11 +26,19 @@ use crate::{
         lod::Lod,
     },
     render::chunk_material::RenderableChunk,
+    utils::index_to_ivec3_bounds,
 };
 use crate::{player::render_distance::Scanner, smooth_transform::SmoothTransformTo};
-use futures_lite::future;
 
-use super::{chunk::Chunk, chunks_refs::ChunkRefs, greedy_mesher_optimized};
+use super::{
+    chunk::{Chunk, ChunkEntities},
+    chunk_budget::{ChunkBudget, ChunkTaskPools, adapt_chunk_budget},
+    chunk_diagnostics::ChunkDiagnosticsPlugin, chunk_interest::ChunkInterest,
+    chunk_priority::{LoadFocus, ScannerView},
+    chunk_queue::ChunkQueue,
+    chunk_status::{ChunkStatus, ChunkStatuses},
+    chunks_refs::ChunkRefs, greedy_mesher_optimized, task_channel::TaskChannel,
+};
 
 pub struct AsyncChunkloaderPlugin;
 impl Plugin for AsyncChunkloaderPlugin {
@@ -32,96 +48,211 @@ impl Plugin for AsyncChunkloaderPlugin {
             "Default LOD must exactly equal the chunk size."
         );
 
+        app.add_systems(First, adapt_chunk_budget);
         app.add_systems(Update, start_worldgen_threads);
         app.add_systems(Update, join_worldgen_threads);
-        app.add_systems(Update, start_mesh_threads);
+        // decorated chunks are the next to be meshed, so they get free worldgen threads first
+        app.add_systems(Update, start_decoration_threads.before(start_worldgen_threads));
+        // meshes may borrow a worldgen thread, so they pick before worldgen does
+        app.add_systems(Update, start_mesh_threads.before(start_decoration_threads));
+        app.add_systems(Update, join_decoration_threads);
+        app.add_systems(
+            Update,
+            advance_chunk_statuses
+                .after(join_worldgen_threads)
+                .after(join_decoration_threads),
+        );
         app.add_systems(Update, join_mesh_threads);
         app.add_systems(Update, unload_chunks);
         app.add_systems(Update, unload_meshes);
         app.init_resource::<AsyncChunkloader>();
         app.init_resource::<Chunks>();
+        app.init_resource::<ChunkEntities>();
+        // the budget's limits come from the pool sizes
+        app.init_resource::<ChunkTaskPools>();
+        app.init_resource::<ChunkBudget>();
+        app.init_resource::<ChunkInterest>();
+        app.add_console_command(FillCommand);
     }
 }
 
-pub const MAX_WORLDGEN_TASKS: usize = 64;
-pub const MAX_MESH_TASKS: usize = 32;
+/// How many meshes removed by `unload_meshes` are kept around in case the chunk is meshed again.
+pub const MESH_CACHE_SIZE: usize = 512;
+
+/// The largest amount of blocks that the `fill` command may replace at once.
+pub const MAX_FILL_VOLUME: i64 = 1 << 20;
 
 #[derive(Resource, Default)]
 pub struct Chunks(pub HashMap<ChunkPosition, Arc<ChunkData>>);
 
 #[derive(Resource, Default)]
 pub struct AsyncChunkloader {
-    pub load_chunk_queue: Vec<ChunkPosition>,
-    pub unload_chunk_queue: Vec<ChunkPosition>,
-    pub load_mesh_queue: Vec<ChunkRefs>,
-    pub unload_mesh_queue: Vec<ChunkPosition>,
-    pub worldgen_tasks: HashMap<ChunkPosition, Task<ChunkData>>,
-    pub mesh_tasks: HashMap<ChunkPosition, Task<Option<RenderableChunk>>>,
+    pub load_chunk_queue: ChunkQueue,
+    pub unload_chunk_queue: HashSet<ChunkPosition>,
+    pub load_mesh_queue: ChunkQueue,
+    pub unload_mesh_queue: HashSet<ChunkPosition>,
+    pub worldgen_tasks: TaskChannel<ChunkData>,
+    pub mesh_tasks: TaskChannel<Option<RenderableChunk>>,
+    /// chunks whose whole neighbourhood has terrain, waiting to be decorated.
+    pub decoration_queue: ChunkQueue,
+    /// `None` if decoration didn't change the chunk.
+    pub decoration_tasks: TaskChannel<Option<ChunkData>>,
+    /// how far along generation each chunk is.
+    pub statuses: ChunkStatuses,
+    /// chunks that became `ChunkStatus::Decorated` during the last `advance_chunk_statuses`.
+    pub recently_decorated: Vec<ChunkPosition>,
+    // decides which queued chunks are started first.
+    focus: LoadFocus,
+    // the data of every loaded chunk as worldgen made it, before decoration.
+    // features are placed from this, so that every neighbour places them the same way.
+    // shares its data with `Chunks` until a chunk is decorated or edited.
+    terrain: HashMap<ChunkPosition, Arc<ChunkData>>,
+    // meshes recently removed by `unload_meshes`. reused if the chunk is meshed again before its data changes.
+    mesh_cache: HashMap<ChunkPosition, Option<RenderableChunk>>,
+    mesh_cache_order: VecDeque<ChunkPosition>,
+    // cached meshes waiting to be put back on their chunk entity by `join_mesh_threads`.
+    restored_meshes: Vec<(ChunkPosition, Option<RenderableChunk>)>,
 }
 
 impl AsyncChunkloader {
-    fn get_chunks_to_load(
-        &mut self,
-        player_position: FloatingPosition,
-    ) -> Drain<'_, ChunkPosition> {
-        let player_chunk_position: ChunkPosition = player_position.into();
-
-        let tasks_left = (MAX_WORLDGEN_TASKS as i32 - self.worldgen_tasks.len() as i32)
-            .min(self.load_chunk_queue.len() as i32)
-            .max(0) as usize;
-
-        self.load_chunk_queue.sort_by(|a, b| {
-            a.0.distance_squared(player_chunk_position.0)
-                .cmp(&b.0.distance_squared(player_chunk_position.0))
-        });
+    fn priority(&self, chunk_position: ChunkPosition) -> i64 {
+        self.focus.priority(chunk_position)
+    }
 
-        self.load_chunk_queue.drain(0..tasks_left)
+    /// Queues worldgen for a chunk, aborting its unload if there is one pending.
+    pub fn queue_chunk_load(&mut self, chunk_position: ChunkPosition) {
+        let priority = self.priority(chunk_position);
+        self.load_chunk_queue.push(chunk_position, priority);
+        self.unload_chunk_queue.remove(&chunk_position);
     }
 
-    fn get_chunks_to_unload(&mut self) -> Drain<'_, ChunkPosition> {
-        self.unload_chunk_queue.drain(..)
+    /// Queues a mesh for a chunk, aborting its unmesh if there is one pending.
+    /// The chunk's neighbours must still be loaded when the mesh task starts, otherwise it is dropped.
+    pub fn queue_mesh(&mut self, chunk_position: ChunkPosition) {
+        let priority = self.priority(chunk_position);
+        self.load_mesh_queue.push(chunk_position, priority);
+        self.unload_mesh_queue.remove(&chunk_position);
     }
 
-    fn get_chunks_to_mesh(&mut self, player_position: FloatingPosition) -> Drain<'_, ChunkRefs> {
-        let player_chunk_position: ChunkPosition = player_position.into();
+    pub fn cancel_chunk_load(&mut self, chunk_position: ChunkPosition) {
+        self.load_chunk_queue.remove(&chunk_position);
+    }
 
-        let tasks_left = (MAX_MESH_TASKS as i32 - self.mesh_tasks.len() as i32)
-            .min(self.load_mesh_queue.len() as i32)
-            .max(0) as usize;
+    pub fn cancel_mesh(&mut self, chunk_position: ChunkPosition) {
+        self.load_mesh_queue.remove(&chunk_position);
+    }
 
-        self.load_mesh_queue.sort_by(|a, b| {
-            a.center_chunk_position
-                .0
-                .distance_squared(player_chunk_position.0)
-                .cmp(
-                    &b.center_chunk_position
-                        .0
-                        .distance_squared(player_chunk_position.0),
-                )
-        });
+    /// Reorders the queues for a new focus. Does nothing if the focus barely changed.
+    pub fn set_focus(&mut self, focus: LoadFocus) {
+        if !focus.differs_from(&self.focus) {
+            return;
+        }
+        self.focus = focus;
+        let focus = &self.focus;
+        self.load_chunk_queue
+            .reprioritize(|chunk_position| focus.priority(chunk_position));
+        self.load_mesh_queue
+            .reprioritize(|chunk_position| focus.priority(chunk_position));
+        self.decoration_queue
+            .reprioritize(|chunk_position| focus.priority(chunk_position));
+    }
+
+    /// Queues decoration for a chunk, unless it is already queued or running.
+    fn queue_decoration(&mut self, chunk_position: ChunkPosition) {
+        if self.decoration_queue.contains(&chunk_position)
+            || self.decoration_tasks.contains(chunk_position)
+        {
+            return;
+        }
+        let priority = self.priority(chunk_position);
+        self.decoration_queue.push(chunk_position, priority);
+    }
+
+    /// Puts back the mesh of a chunk that was recently unmeshed, instead of meshing it again.
+    /// Returns false if there is no cached mesh for this chunk.
+    pub fn restore_cached_mesh(&mut self, chunk_position: ChunkPosition) -> bool {
+        let Some(renderable_chunk) = self.mesh_cache.remove(&chunk_position) else {
+            return false;
+        };
+        self.restored_meshes.push((chunk_position, renderable_chunk));
+        true
+    }
+
+    fn cache_mesh(&mut self, chunk_position: ChunkPosition, renderable_chunk: Option<RenderableChunk>) {
+        if self.mesh_cache_order.len() >= MESH_CACHE_SIZE {
+            if let Some(oldest) = self.mesh_cache_order.pop_front() {
+                self.mesh_cache.remove(&oldest);
+            }
+        }
+        self.mesh_cache.insert(chunk_position, renderable_chunk);
+        self.mesh_cache_order.push_back(chunk_position);
+    }
+
+    /// Throws away the mesh of a chunk, wherever it is. Use this when the chunk's data changes.
+    pub fn invalidate_mesh(&mut self, chunk_position: ChunkPosition) {
+        self.mesh_tasks.cancel(chunk_position);
+        self.mesh_cache.remove(&chunk_position);
+        self.statuses.downgrade(chunk_position, ChunkStatus::Decorated);
+        self.cancel_mesh(chunk_position);
+    }
 
-        self.load_mesh_queue.drain(0..tasks_left)
+    /// Tasks running on the worldgen pool: worldgen, decoration, and meshes that didn't fit in the mesh pool.
+    fn worldgen_pool_in_flight(&self, budget: &ChunkBudget) -> usize {
+        self.worldgen_tasks.in_flight()
+            + self.decoration_tasks.in_flight()
+            + self.mesh_tasks.in_flight().saturating_sub(budget.mesh_tasks)
     }
 
-    fn get_chunks_to_unmesh(&mut self) -> Drain<'_, ChunkPosition> {
-        self.unload_mesh_queue.drain(..)
+    /// How many more worldgen or decoration tasks may be started.
+    /// The last free thread is left to meshing while meshes are queued.
+    fn worldgen_slots(&self, budget: &ChunkBudget) -> usize {
+        let reserved = usize::from(!self.load_mesh_queue.is_empty());
+        budget
+            .worldgen_tasks
+            .saturating_sub(reserved)
+            .saturating_sub(self.worldgen_pool_in_flight(budget))
+    }
+
+    /// Pops up to `count` queued chunks, along with when they were queued.
+    fn get_chunks_to_load(&mut self, count: usize) -> Vec<(ChunkPosition, Instant)> {
+        (0..count)
+            .map_while(|_| self.load_chunk_queue.pop())
+            .collect()
+    }
+
+    fn get_chunks_to_decorate(&mut self, count: usize) -> Vec<ChunkPosition> {
+        (0..count)
+            .map_while(|_| self.decoration_queue.pop())
+            .map(|(chunk_position, _)| chunk_position)
+            .collect()
+    }
+
+    fn get_chunks_to_unload(&mut self) -> HashSet<ChunkPosition> {
+        std::mem::take(&mut self.unload_chunk_queue)
+    }
+
+    fn get_chunks_to_mesh(&mut self, count: usize) -> Vec<(ChunkPosition, Instant)> {
+        (0..count)
+            .map_while(|_| self.load_mesh_queue.pop())
+            .collect()
+    }
+
+    fn get_chunks_to_unmesh(&mut self) -> HashSet<ChunkPosition> {
+        std::mem::take(&mut self.unload_mesh_queue)
     }
 }
 
 fn spawn_chunk_as_bevy_entity(
-    chunk_data: ChunkData,
-    chunk_entities: &mut Chunks,
+    chunk_data: Arc<ChunkData>,
+    chunks: &mut Chunks,
     timer: &Time,
     commands: &mut Commands,
-    chunk_canididates: Query<(Entity, &Chunk)>,
+    chunk_entities: &ChunkEntities,
 ) {
     let chunk_position = chunk_data.position;
-    for (entity_id, chunk) in chunk_canididates.iter() {
-        if chunk.position == chunk_position {
-            if let Ok(mut entity_commands) = commands.get_entity(entity_id) {
-                entity_commands.despawn();
-                break;
-            }
+    if let Some(entity_id) = chunk_entities.get(chunk_position) {
+        if let Ok(mut entity_commands) = commands.get_entity(entity_id) {
+            entity_commands.despawn();
         }
     }
 
@@ -142,128 +273,273 @@ fn spawn_chunk_as_bevy_entity(
         ),
     ));
 
-    chunk_entities
-        .0
-        .insert(chunk_position, Arc::new(chunk_data));
+    chunks.0.insert(chunk_position, chunk_data);
 }
 
 #[allow(clippy::needless_pass_by_value)]
 fn start_worldgen_threads(
     mut chunkloader: ResMut<AsyncChunkloader>,
-    block_prototypes: Res<BlockPrototypes>,
-    scanners: Query<&GlobalTransform, With<Scanner>>,
+    world_generator: Option<Res<ActiveWorldGenerator>>,
+    scanners: Query<(&GlobalTransform, &Scanner, Option<&Frustum>)>,
+    task_pools: Res<ChunkTaskPools>,
+    budget: Res<ChunkBudget>,
+    mut diagnostics: Diagnostics,
 ) {
-    let task_pool = AsyncComputeTaskPool::get();
-    let scanner = scanners.single().unwrap();
-    let player_position = FloatingPosition(scanner.translation());
+    chunkloader.set_focus(LoadFocus(
+        scanners
+            .iter()
+            .map(|(transform, scanner, frustum)| {
+                ScannerView::new(transform, scanner.velocity, frustum)
+            })
+            .collect(),
+    ));
 
-    let to_load: Vec<ChunkPosition> = chunkloader.get_chunks_to_load(player_position).collect();
-    for chunk_position in to_load {
-        let prototypes = block_prototypes.clone();
-        let task = task_pool.spawn(async move { ChunkData::generate(&prototypes, chunk_position) });
-        chunkloader.worldgen_tasks.insert(chunk_position, task);
+    // the generator is created once the prototypes are loaded
+    let Some(world_generator) = world_generator else {
+        return;
+    };
+
+    let slots = chunkloader.worldgen_slots(&budget);
+    let to_load = chunkloader.get_chunks_to_load(slots);
+    let mut queue_waits = Vec::with_capacity(to_load.len());
+    for (chunk_position, queued_at) in to_load {
+        queue_waits.push(queued_at.elapsed());
+        let world_generator = world_generator.0.clone();
+        chunkloader
+            .worldgen_tasks
+            .spawn(&task_pools.worldgen, chunk_position, move || {
+                world_generator.generate(chunk_position)
+            });
     }
+
+    ChunkDiagnosticsPlugin::measure_durations(
+        &mut diagnostics,
+        &ChunkDiagnosticsPlugin::WORLDGEN_QUEUE_WAIT,
+        &queue_waits,
+    );
 }
 
 #[allow(clippy::needless_pass_by_value)]
 fn join_worldgen_threads(
     mut chunkloader: ResMut<AsyncChunkloader>,
-    mut chunk_entities: ResMut<Chunks>,
+    mut chunks: ResMut<Chunks>,
     timer: Res<Time>,
     mut commands: Commands,
-    chunk_canididates: Query<(Entity, &Chunk)>,
+    chunk_entities: Res<ChunkEntities>,
+    budget: Res<ChunkBudget>,
+    mut diagnostics: Diagnostics,
 ) {
-    chunkloader.worldgen_tasks.retain(|_, task| {
-        // check on our worldgen task to see how it's doing :)
-        let status = block_on(future::poll_once(task));
+    let mut task_durations = vec![];
+    for result in chunkloader.worldgen_tasks.drain_finished(budget.joins_per_frame) {
+        task_durations.push(result.duration);
+        chunkloader.statuses.set(result.position, ChunkStatus::Terrain);
+        let chunk_data = Arc::new(result.value);
+        chunkloader.terrain.insert(result.position, chunk_data.clone());
+        spawn_chunk_as_bevy_entity(chunk_data, &mut chunks, &timer, &mut commands, &chunk_entities);
+    }
+
+    ChunkDiagnosticsPlugin::measure_durations(
+        &mut diagnostics,
+        &ChunkDiagnosticsPlugin::WORLDGEN_TIME,
+        &task_durations,
+    );
+    ChunkDiagnosticsPlugin::measure_rate(
+        &mut diagnostics,
+        &ChunkDiagnosticsPlugin::CHUNKS_GENERATED_PER_SECOND,
+        task_durations.len(),
+        &timer,
+    );
+}
 
-        // keep the entry in our task vector only if the task is not done yet
-        let retain = status.is_none();
+#[allow(clippy::needless_pass_by_value)]
+fn start_decoration_threads(
+    mut chunkloader: ResMut<AsyncChunkloader>,
+    world_generator: Option<Res<ActiveWorldGenerator>>,
+    task_pools: Res<ChunkTaskPools>,
+    budget: Res<ChunkBudget>,
+) {
+    let Some(world_generator) = world_generator else {
+        return;
+    };
+
+    // decoration is worldgen too, so it shares the worldgen budget and pool
+    let slots = chunkloader.worldgen_slots(&budget);
+    for chunk_position in chunkloader.get_chunks_to_decorate(slots) {
+        // a neighbour may have been unloaded while this chunk was queued.
+        // `advance_chunk_statuses` queues it again once the neighbour is back.
+        let Some(terrain) = ChunkRefs::try_from_map(&chunkloader.terrain, chunk_position) else {
+            continue;
+        };
+        let world_generator = world_generator.0.clone();
+        chunkloader
+            .decoration_tasks
+            .spawn(&task_pools.worldgen, chunk_position, move || {
+                world_generator.decorate(&terrain)
+            });
+    }
+}
 
-        // if this task is done, handle the data it returned!
-        if let Some(chunk_component) = status {
-            spawn_chunk_as_bevy_entity(chunk_component, &mut chunk_entities, &timer, &mut commands, chunk_canididates);
+#[allow(clippy::needless_pass_by_value)]
+fn join_decoration_threads(
+    mut chunkloader: ResMut<AsyncChunkloader>,
+    mut chunks: ResMut<Chunks>,
+    budget: Res<ChunkBudget>,
+) {
+    for result in chunkloader.decoration_tasks.drain_finished(budget.joins_per_frame) {
+        if let Some(chunk_data) = result.value {
+            chunks.0.insert(result.position, Arc::new(chunk_data));
         }
+        chunkloader.statuses.set(result.position, ChunkStatus::Decorated);
+    }
+}
 
-        retain
-    });
+/// Queues chunks for decoration as soon as their neighbours allow it, and remembers which chunks were decorated
+/// so that the chunks around them can be checked for meshing.
+/// Only the neighbourhoods of chunks whose status changed are checked.
+fn advance_chunk_statuses(mut chunkloader: ResMut<AsyncChunkloader>) {
+    let changed = chunkloader.statuses.take_changed();
+    let recently_decorated: Vec<ChunkPosition> = changed
+        .iter()
+        .copied()
+        .filter(|&chunk_position| chunkloader.statuses.get(chunk_position) == ChunkStatus::Decorated)
+        .collect();
+    let candidates: HashSet<ChunkPosition> = changed
+        .into_iter()
+        .flat_map(|chunk_position| {
+            (0..27).map(move |i| {
+                chunk_position + ChunkPosition(index_to_ivec3_bounds(i, 3) + IVec3::NEG_ONE)
+            })
+        })
+        .collect();
+
+    for chunk_position in candidates {
+        // decoration runs on the worldgen pool and meshing on the mesh pool, so chunks are only queued here.
+        let statuses = &chunkloader.statuses;
+        if statuses.get(chunk_position) == ChunkStatus::Terrain
+            && statuses.can_advance_to(chunk_position, ChunkStatus::Decorated)
+        {
+            chunkloader.queue_decoration(chunk_position);
+        }
+    }
+    chunkloader.recently_decorated = recently_decorated;
 }
 
 #[allow(clippy::needless_pass_by_value)]
 fn start_mesh_threads(
     mut chunkloader: ResMut<AsyncChunkloader>,
-    scanners: Query<&GlobalTransform, With<Scanner>>,
+    chunks: Res<Chunks>,
+    task_pools: Res<ChunkTaskPools>,
+    budget: Res<ChunkBudget>,
+    mut diagnostics: Diagnostics,
 ) {
-    let task_pool = AsyncComputeTaskPool::get();
-    let scanner = scanners.single().unwrap();
-    let player_position = FloatingPosition(scanner.translation());
-
-    let to_mesh: Vec<ChunkRefs> = chunkloader.get_chunks_to_mesh(player_position).collect();
-    for chunk_refs in to_mesh {
-        let k = chunk_refs.center_chunk_position;
-        let task = task_pool.spawn(async move {
+    let mesh_slots = budget
+        .mesh_tasks
+        .saturating_sub(chunkloader.mesh_tasks.in_flight());
+    // once the mesh pool is full, one more mesh may run on a free worldgen thread
+    let borrowed =
+        usize::from(budget.worldgen_tasks > chunkloader.worldgen_pool_in_flight(&budget));
+    let to_mesh = chunkloader.get_chunks_to_mesh(mesh_slots + borrowed);
+    let mut queue_waits = Vec::with_capacity(to_mesh.len());
+    for (i, (k, queued_at)) in to_mesh.into_iter().enumerate() {
+        queue_waits.push(queued_at.elapsed());
+        // a neighbour may have been unloaded while this chunk was queued.
+        // the scanner queues it again once the neighbour is back.
+        let Some(chunk_refs) = ChunkRefs::try_new(&chunks, k) else {
+            continue;
+        };
+        let task_pool = if i < mesh_slots {
+            &task_pools.mesh
+        } else {
+            &task_pools.worldgen
+        };
+        chunkloader.mesh_tasks.spawn(task_pool, k, move || {
             greedy_mesher_optimized::build_chunk_instance_data(
                 &chunk_refs,
                 super::lod::Lod::default(),
             )
         });
-        chunkloader.mesh_tasks.insert(k, task);
     }
+
+    ChunkDiagnosticsPlugin::measure_durations(
+        &mut diagnostics,
+        &ChunkDiagnosticsPlugin::MESH_QUEUE_WAIT,
+        &queue_waits,
+    );
 }
 
 #[allow(clippy::needless_pass_by_value)]
 fn join_mesh_threads(
     mut chunkloader: ResMut<AsyncChunkloader>,
-    chunk_canididates: Query<(Entity, &Chunk)>,
+    chunk_entities: Res<ChunkEntities>,
     mut commands: Commands,
+    timer: Res<Time>,
+    budget: Res<ChunkBudget>,
+    mut diagnostics: Diagnostics,
 ) {
-    chunkloader.mesh_tasks.retain(|chunk_position, task| {
-        // check on our mesh task to see how it's doing :)
-        let status = block_on(future::poll_once(task));
-
-        // keep the entry in our task vector only if the task is not done yet
-        let Some(renderable_chunk_optional) = status else {
-            return true;
+    let mut task_durations = vec![];
+    let finished: Vec<_> = chunkloader
+        .mesh_tasks
+        .drain_finished(budget.joins_per_frame)
+        .into_iter()
+        .map(|result| {
+            task_durations.push(result.duration);
+            (result.position, result.value)
+        })
+        .collect();
+    let restored = std::mem::take(&mut chunkloader.restored_meshes);
+
+    for (chunk_position, renderable_chunk_optional) in finished.into_iter().chain(restored) {
+        chunkloader.statuses.set(chunk_position, ChunkStatus::Meshed);
+
+        let Some(entity_id) = chunk_entities.get(chunk_position) else {
+            continue;
         };
-
-        // if this task is done, handle the data it returned!
-        if let Some(renderable_chunk) = renderable_chunk_optional {
-            // todo: refactor to use bevy indexes when the update drops.
-            for (entity_id, chunk) in chunk_canididates.iter() {
-                if chunk.position == *chunk_position {
-                    if let Ok(mut entity_commands) = commands.get_entity(entity_id) {
-                        entity_commands.insert(renderable_chunk);
-                        break;
-                    }
-                }
-            }
+        if let Ok(mut entity_commands) = commands.get_entity(entity_id) {
+            // a remeshed chunk may have become entirely culled
+            match renderable_chunk_optional {
+                Some(renderable_chunk) => entity_commands.insert(renderable_chunk),
+                None => entity_commands.try_remove::<RenderableChunk>(),
+            };
         }
+    }
 
-        false
-    });
+    ChunkDiagnosticsPlugin::measure_durations(
+        &mut diagnostics,
+        &ChunkDiagnosticsPlugin::MESH_TIME,
+        &task_durations,
+    );
+    ChunkDiagnosticsPlugin::measure_rate(
+        &mut diagnostics,
+        &ChunkDiagnosticsPlugin::CHUNKS_MESHED_PER_SECOND,
+        task_durations.len(),
+        &timer,
+    );
 }
 
 #[allow(clippy::needless_pass_by_value)]
 fn unload_chunks(
     mut chunkloader: ResMut<AsyncChunkloader>,
-    mut chunk_entities: ResMut<Chunks>,
-    chunk_canididates: Query<(Entity, &Chunk)>,
+    mut chunks: ResMut<Chunks>,
+    chunk_entities: Res<ChunkEntities>,
     mut commands: Commands,
 ) {
-    let to_unload: HashSet<ChunkPosition> = chunkloader.get_chunks_to_unload().collect();
+    let to_unload = chunkloader.get_chunks_to_unload();
 
-    // todo: refactor to use bevy indexes when the update drops.
-    for (entity_id, chunk) in chunk_canididates.iter() {
-        if to_unload.contains(&chunk.position) {
+    for chunk_position in to_unload {
+        if let Some(entity_id) = chunk_entities.get(chunk_position) {
             if let Ok(mut entity_commands) = commands.get_entity(entity_id) {
                 entity_commands.despawn();
             }
         }
-    }
-
-    for chunk_position in to_unload {
-        chunk_entities.0.remove(&chunk_position);
-        chunkloader.worldgen_tasks.remove(&chunk_position);
+        chunks.0.remove(&chunk_position);
+        chunkloader.terrain.remove(&chunk_position);
+        chunkloader.worldgen_tasks.cancel(chunk_position);
+        chunkloader.cancel_chunk_load(chunk_position);
+        chunkloader.decoration_tasks.cancel(chunk_position);
+        chunkloader.decoration_queue.remove(&chunk_position);
+        // the entity and its mesh are gone with the data
+        chunkloader.invalidate_mesh(chunk_position);
+        chunkloader.statuses.set(chunk_position, ChunkStatus::Empty);
     }
 }
 
@@ -271,16 +547,157 @@ fn unload_chunks(
 fn unload_meshes(
     mut chunkloader: ResMut<AsyncChunkloader>,
     mut commands: Commands,
-    chunk_canididates: Query<(Entity, &Chunk)>,
+    chunk_entities: Res<ChunkEntities>,
+    renderable_chunks: Query<&RenderableChunk>,
 ) {
-    let to_unload: HashSet<ChunkPosition> = chunkloader.get_chunks_to_unmesh().collect();
+    let to_unload = chunkloader.get_chunks_to_unmesh();
 
-    // todo: refactor to use bevy indexes when the update drops.
-    for (entity_id, chunk) in chunk_canididates.iter() {
-        if to_unload.contains(&chunk.position) {
+    for chunk_position in to_unload {
+        if let Some(entity_id) = chunk_entities.get(chunk_position) {
             if let Ok(mut entity_commands) = commands.get_entity(entity_id) {
                 entity_commands.try_remove::<RenderableChunk>();
             }
+            if chunkloader.statuses.get(chunk_position) == ChunkStatus::Meshed {
+                chunkloader.statuses.set(chunk_position, ChunkStatus::Decorated);
+                let renderable_chunk = renderable_chunks.get(entity_id).ok().cloned();
+                chunkloader.cache_mesh(chunk_position, renderable_chunk);
+            }
+        }
+        chunkloader.mesh_tasks.cancel(chunk_position);
+        chunkloader.cancel_mesh(chunk_position);
+    }
+}
+
+/// `fill <x1> <y1> <z1> <x2> <y2> <z2> <block>`
+/// Replaces every block in the box between the two corners and remeshes the affected chunks.
+struct FillCommand;
+
+impl ConsoleCommand for FillCommand {
+    fn name(&self) -> &'static str {
+        "fill"
+    }
+
+    fn usage(&self) -> &'static str {
+        "fill <x1> <y1> <z1> <x2> <y2> <z2> <block>"
+    }
+
+    fn run(&self, args: &[&str], world: &mut World) -> Result<String> {
+        let corner = |offset: usize| -> Result<IVec3> {
+            Ok(IVec3::new(
+                parse_arg(args, offset, "x")?,
+                parse_arg(args, offset + 1, "y")?,
+                parse_arg(args, offset + 2, "z")?,
+            ))
+        };
+        let (first, second) = (corner(0)?, corner(3)?);
+        let block_name = args.get(6).ok_or_else(|| anyhow!("Missing argument <block>."))?;
+        let block = world
+            .resource::<BlockPrototypes>()
+            .get(block_name)
+            .ok_or_else(|| anyhow!("Unknown block `{block_name}`."))?;
+
+        let min = first.min(second);
+        let max = first.max(second);
+        let volume = (max.as_i64vec3() - min.as_i64vec3() + I64Vec3::ONE).element_product();
+        ensure!(
+            volume <= MAX_FILL_VOLUME,
+            "Can not fill {volume} blocks at once. The limit is {MAX_FILL_VOLUME}."
+        );
+
+        let min_chunk = ChunkPosition::from(Position(min));
+        let max_chunk = ChunkPosition::from(Position(max));
+        // decoration replaces a chunk with one built from its undecorated terrain, which would undo the fill.
+        let statuses = &world.resource::<AsyncChunkloader>().statuses;
+        let decorated: HashSet<ChunkPosition> = (min_chunk.z..=max_chunk.z)
+            .flat_map(|cz| {
+                (min_chunk.y..=max_chunk.y).flat_map(move |cy| {
+                    (min_chunk.x..=max_chunk.x).map(move |cx| ChunkPosition::new(cx, cy, cz))
+                })
+            })
+            .filter(|&chunk_position| statuses.get(chunk_position) >= ChunkStatus::Decorated)
+            .collect();
+
+        let mut changed_chunks = Vec::new();
+        let mut skipped_chunks = 0;
+        let mut filled: i64 = 0;
+        let mut chunks = world.resource_mut::<Chunks>();
+        for cz in min_chunk.z..=max_chunk.z {
+            for cy in min_chunk.y..=max_chunk.y {
+                for cx in min_chunk.x..=max_chunk.x {
+                    let chunk_position = ChunkPosition::new(cx, cy, cz);
+                    let chunk_data = chunks
+                        .0
+                        .get_mut(&chunk_position)
+                        .filter(|_| decorated.contains(&chunk_position));
+                    let Some(chunk_data) = chunk_data else {
+                        skipped_chunks += 1;
+                        continue;
+                    };
+                    // meshing tasks may still be reading the old data, so copy on write.
+                    let chunk_data = Arc::make_mut(chunk_data);
+
+                    let origin = Position::from(chunk_position).0;
+                    let local_min = (min - origin).max(IVec3::ZERO);
+                    let local_max = (max - origin).min(IVec3::splat(CHUNK_SIZE_I32 - 1));
+                    chunk_data.fill_box(Position(local_min), Position(local_max), block);
+                    filled += (local_max - local_min + IVec3::ONE).as_i64vec3().element_product();
+                    changed_chunks.push(chunk_position);
+                }
+            }
+        }
+
+        // blocks on a chunk border change the faces of the neighbouring chunk as well.
+        let affected: HashSet<ChunkPosition> = changed_chunks
+            .iter()
+            .flat_map(|&chunk_position| {
+                (0..27).map(move |i| {
+                    chunk_position + ChunkPosition(index_to_ivec3_bounds(i, 3) + IVec3::NEG_ONE)
+                })
+            })
+            .collect();
+        // only chunks some scanner still wants meshed are remeshed.
+        let to_remesh: Vec<ChunkPosition> = {
+            let interest = world.resource::<ChunkInterest>();
+            affected
+                .iter()
+                .copied()
+                .filter(|&chunk_position| interest.is_mesh_held(chunk_position))
+                .collect()
+        };
+        let mut chunkloader = world.resource_mut::<AsyncChunkloader>();
+        // in-flight and cached meshes were built from the old data, even those of chunks nobody holds,
+        // which would otherwise be restored from the cache when they come back into view.
+        for &chunk_position in &affected {
+            chunkloader.invalidate_mesh(chunk_position);
+        }
+        for chunk_position in to_remesh {
+            if chunkloader
+                .statuses
+                .neighbourhood_reached(chunk_position, ChunkStatus::Decorated)
+            {
+                chunkloader.queue_mesh(chunk_position);
+            }
+        }
+
+        let mut message = format!("Filled {filled} blocks with {block_name}.");
+        if skipped_chunks > 0 {
+            message += &format!(" Skipped {skipped_chunks} chunks that aren't loaded or decorated yet.");
+        }
+        Ok(message)
+    }
+
+    fn complete(&self, args: &[&str], world: &World) -> Vec<String> {
+        if args.len() != 6 {
+            return vec![];
         }
+        world
+            .get_resource::<BlockPrototypes>()
+            .map(|block_prototypes| {
+                block_prototypes
+                    .iter()
+                    .map(|(name, _)| (*name).to_string())
+                    .collect()
+            })
+            .unwrap_or_default()
     }
 }
@@ -1,7 +1,10 @@
 use std::sync::OnceLock;
 
-use bevy::prelude::*;
-use bracket_noise::prelude::*;
+use bevy::{
+    ecs::{component::HookContext, world::DeferredWorld},
+    platform::collections::HashMap,
+    prelude::*,
+};
 
 use crate::{
     mod_manager::prototypes::{BlockPrototype, BlockPrototypes, Prototypes},
@@ -26,18 +29,78 @@ pub const CHUNK_SIZE3_I32: i32 = (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE) as i32;
 pub const CHUNK_INITIAL_Y_OFFSET: f32 = -64.;
 pub const CHUNK_FLOAT_UP_BLOCKS_PER_SECOND: f32 = 32.;
 
+/// Chunks are immutable so that [`ChunkEntities`] can never go out of date.
 #[derive(Component)]
+#[component(immutable, on_insert = index_chunk, on_replace = unindex_chunk)]
 pub struct Chunk {
     pub position: ChunkPosition,
 }
 
-#[derive(Debug)]
+/// Finds the entity of a loaded chunk without iterating over every chunk.
+/// Kept in sync by the hooks on [`Chunk`].
+#[derive(Resource, Default)]
+pub struct ChunkEntities(HashMap<ChunkPosition, Entity>);
+
+impl ChunkEntities {
+    #[must_use]
+    pub fn get(&self, chunk_position: ChunkPosition) -> Option<Entity> {
+        self.0.get(&chunk_position).copied()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (ChunkPosition, Entity)> + '_ {
+        self.0
+            .iter()
+            .map(|(&chunk_position, &entity)| (chunk_position, entity))
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+fn index_chunk(mut world: DeferredWorld, context: HookContext) {
+    let Some(position) = world
+        .get::<Chunk>(context.entity)
+        .map(|chunk| chunk.position)
+    else {
+        return;
+    };
+    let previous = world
+        .resource_mut::<ChunkEntities>()
+        .0
+        .insert(position, context.entity);
+    if let Some(previous) = previous.filter(|&previous| previous != context.entity) {
+        warn!("Chunk {position:?} was spawned twice, {previous} is no longer indexed.");
+    }
+}
+
+fn unindex_chunk(mut world: DeferredWorld, context: HookContext) {
+    let Some(position) = world
+        .get::<Chunk>(context.entity)
+        .map(|chunk| chunk.position)
+    else {
+        return;
+    };
+    let mut chunk_entities = world.resource_mut::<ChunkEntities>();
+    // the position may already belong to a newer entity
+    if chunk_entities.get(position) == Some(context.entity) {
+        chunk_entities.0.remove(&position);
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct ChunkData {
     pub position: ChunkPosition,
     voxels: Voxels,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 enum Voxels {
     Heterogeneous(Box<[ThinBlockPointer]>),
     Homogeneous(ThinBlockPointer),
@@ -54,8 +117,19 @@ impl ChunkData {
         .expect("Invalid thin block pointer.")
     }
 
+    /// The id of the block at `index`. Unlike [`ChunkData::get_block`] this doesn't need the block registry.
+    #[inline]
+    #[must_use]
+    pub fn get_block_id(&self, index: VoxelIndex) -> u16 {
+        match &self.voxels {
+            Voxels::Homogeneous(block_pointer) => *block_pointer,
+            Voxels::Heterogeneous(voxels) => voxels[index.i()],
+        }
+    }
+
     pub fn set_block(&mut self, index: VoxelIndex, block_type: &'static BlockPrototype) {
         match &mut self.voxels {
+            Voxels::Homogeneous(old_block_type) if *old_block_type == block_type.id => {}
             Voxels::Homogeneous(old_block_type) => {
                 let mut new_voxels: Box<[ThinBlockPointer]> =
                     (0..CHUNK_SIZE3).map(|_| *old_block_type).collect();
@@ -67,11 +141,39 @@ impl ChunkData {
 
                 let homogeneous = voxels.iter().all(|&block| block == block_type.id);
                 if homogeneous {
-                    todo!("woo hoo");
-                    //self.voxels = Voxels::Homogeneous(block_type);
+                    self.voxels = Voxels::Homogeneous(block_type.id);
+                }
+            }
+        }
+    }
+
+    /// Sets every block in the box between `min` and `max`, both inclusive and local to the chunk.
+    /// Much faster than calling [`ChunkData::set_block`] for every block, which checks the whole chunk every time.
+    pub fn fill_box(&mut self, min: Position, max: Position, block_type: &'static BlockPrototype) {
+        if min.0 == IVec3::ZERO && max.0 == IVec3::splat(CHUNK_SIZE_I32 - 1) {
+            self.voxels = Voxels::Homogeneous(block_type.id);
+            return;
+        }
+        if let Voxels::Homogeneous(old_block_type) = self.voxels {
+            if old_block_type == block_type.id {
+                return;
+            }
+            self.voxels = Voxels::Heterogeneous(vec![old_block_type; CHUNK_SIZE3].into());
+        }
+        let Voxels::Heterogeneous(voxels) = &mut self.voxels else {
+            unreachable!("Homogeneous chunks were made heterogeneous above.");
+        };
+
+        for z in min.z..=max.z {
+            for y in min.y..=max.y {
+                for x in min.x..=max.x {
+                    voxels[VoxelIndex::from(Position::new(x, y, z)).i()] = block_type.id;
                 }
             }
         }
+        if voxels.iter().all(|&block| block == block_type.id) {
+            self.voxels = Voxels::Homogeneous(block_type.id);
+        }
     }
 
     #[inline]
@@ -175,63 +277,27 @@ pub fn set_block_registry(block_prototypes: &BlockPrototypes) {
 }
 
 impl ChunkData {
-    /// use noise shape our voxel data based on the `chunk_pos`
+    /// A chunk filled with a single block.
     #[must_use]
-    pub fn generate(block_prototypes: &BlockPrototypes, chunk_position: ChunkPosition) -> Self {
-        // hardcoded extremity check
-        if chunk_position.y * CHUNK_SIZE_I32 > 285 {
-            return Self {
-                voxels: Voxels::Homogeneous(block_prototypes.get("air").unwrap().id),
-                position: chunk_position,
-            };
-        }
-        // hardcoded extremity check
-        if chunk_position.y * CHUNK_SIZE_I32 < -160 {
-            return Self {
-                voxels: Voxels::Homogeneous(block_prototypes.get("grass").unwrap().id),
-                position: chunk_position,
-            };
+    pub const fn homogeneous(
+        chunk_position: ChunkPosition,
+        block: &'static BlockPrototype,
+    ) -> Self {
+        Self {
+            voxels: Voxels::Homogeneous(block.id),
+            position: chunk_position,
         }
+    }
 
-        let world_position = Position::from(chunk_position);
-        let mut fast_noise = FastNoise::new();
-        fast_noise.set_frequency(0.0254);
-        let mut x = 0;
-        let mut y = 0;
-        let mut z = 0;
-
-        let voxels: Box<[ThinBlockPointer; CHUNK_SIZE3]> = std::array::from_fn(|_| {
-            let wx = (x + world_position.x) as f32;
-            let wy = (y + world_position.y) as f32 - 200.;
-            let wz = (z + world_position.z) as f32;
-
-            let scale = 1.0;
-            fast_noise.set_frequency(0.0254);
-            let overhang = fast_noise.get_noise3d(wx * scale, wy, wz * scale) * 55.0;
-            fast_noise.set_frequency(0.002591);
-            let noise_2 = fast_noise.get_noise(wx + overhang, wz / 3.0);
-            let h = noise_2 * 30.0;
-            let solid = h > wy;
-
-            let block_type = if !solid {
-                block_prototypes.get("air").unwrap()
-            } else {
-                block_prototypes.get("grass").unwrap()
-            };
-
-            x += 1;
-            if x == CHUNK_SIZE_I32 {
-                y += 1;
-                x = 0;
-                if y == CHUNK_SIZE_I32 {
-                    z += 1;
-                    y = 0;
-                }
-            }
-
-            block_type.id
-        })
-        .into();
+    /// Builds a chunk by asking for the block at every local position, in index order.
+    /// The chunk is stored as homogeneous if every block turns out the same.
+    pub fn from_fn(
+        chunk_position: ChunkPosition,
+        mut block_at: impl FnMut(Position) -> &'static BlockPrototype,
+    ) -> Self {
+        let voxels: Box<[ThinBlockPointer]> = (0..CHUNK_SIZE3)
+            .map(|i| block_at(VoxelIndex(i).into()).id)
+            .collect();
 
         if let Some(&first) = voxels.first() {
             let homogeneous = voxels.iter().all(|&block_type| block_type == first);
@@ -0,0 +1,122 @@
+//! Adapts how much chunk work is done per frame to a target frame time,
+//! and gives worldgen and meshing their own thread pools so that they don't wait on each other.
+
+use std::{thread::available_parallelism, time::Duration};
+
+use bevy::{
+    prelude::*,
+    tasks::{TaskPool, TaskPoolBuilder},
+};
+
+/// The in-flight task limits never drop below these, so chunks keep loading on slow machines.
+/// They never go above the number of threads in the pool either, see [`ChunkTaskPools`].
+pub const MIN_WORLDGEN_TASKS: usize = 2;
+pub const MIN_MESH_TASKS: usize = 2;
+
+pub const MIN_JOINS_PER_FRAME: usize = 4;
+pub const MAX_JOINS_PER_FRAME: usize = 256;
+
+/// Frames within this fraction of the target frame time leave the budget unchanged.
+const FRAME_TIME_TOLERANCE: f32 = 0.1;
+/// How much the budget shrinks after a slow frame. It grows back by one per fast frame.
+const DECREASE_FACTOR: f32 = 0.75;
+
+/// How much chunk work may be in flight, and how many results may be applied each frame.
+/// Shrinks quickly when frames take longer than `target_frame_time`, and grows slowly when they're faster.
+#[derive(Resource)]
+pub struct ChunkBudget {
+    pub target_frame_time: Duration,
+    pub worldgen_tasks: usize,
+    pub mesh_tasks: usize,
+    pub joins_per_frame: usize,
+    /// the number of threads in the worldgen pool
+    max_worldgen_tasks: usize,
+    /// the number of threads in the mesh pool
+    max_mesh_tasks: usize,
+}
+
+impl FromWorld for ChunkBudget {
+    fn from_world(world: &mut World) -> Self {
+        Self::new(world.get_resource_or_init::<ChunkTaskPools>().as_ref())
+    }
+}
+
+impl ChunkBudget {
+    /// Starts with a task for every thread of the pools.
+    #[must_use]
+    pub fn new(task_pools: &ChunkTaskPools) -> Self {
+        let max_worldgen_tasks = task_pools.worldgen.thread_num();
+        let max_mesh_tasks = task_pools.mesh.thread_num();
+        Self {
+            target_frame_time: Duration::from_secs(1) / 60,
+            worldgen_tasks: max_worldgen_tasks,
+            mesh_tasks: max_mesh_tasks,
+            joins_per_frame: 32,
+            max_worldgen_tasks,
+            max_mesh_tasks,
+        }
+    }
+
+    /// Moves the budget towards what fits in the target frame time, given how long the last frame took.
+    pub fn adapt(&mut self, frame_time: Duration) {
+        let ratio = frame_time.as_secs_f32() / self.target_frame_time.as_secs_f32();
+        let adapt = |value: usize, min: usize, max: usize| {
+            let min = min.min(max);
+            if ratio > 1. + FRAME_TIME_TOLERANCE {
+                ((value as f32 * DECREASE_FACTOR) as usize).max(min)
+            } else if ratio < 1. - FRAME_TIME_TOLERANCE {
+                (value + 1).min(max)
+            } else {
+                value
+            }
+        };
+        self.worldgen_tasks = adapt(
+            self.worldgen_tasks,
+            MIN_WORLDGEN_TASKS,
+            self.max_worldgen_tasks,
+        );
+        self.mesh_tasks = adapt(self.mesh_tasks, MIN_MESH_TASKS, self.max_mesh_tasks);
+        self.joins_per_frame = adapt(self.joins_per_frame, MIN_JOINS_PER_FRAME, MAX_JOINS_PER_FRAME);
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+pub fn adapt_chunk_budget(mut budget: ResMut<ChunkBudget>, time: Res<Time<Real>>) {
+    budget.adapt(time.delta());
+}
+
+/// Separate pools for worldgen and meshing.
+/// Meshing nearby chunks is what the player sees, so it never queues behind far away worldgen:
+/// while meshes are queued, worldgen leaves its last free thread to meshes that don't fit in the mesh pool.
+///
+/// A pool runs its tasks in the order they were spawned, so no more tasks are started than it has threads.
+/// Everything else waits in the chunk loader's priority queues, where the nearest chunks are always started first.
+#[derive(Resource)]
+pub struct ChunkTaskPools {
+    pub worldgen: TaskPool,
+    pub mesh: TaskPool,
+}
+
+impl Default for ChunkTaskPools {
+    /// Worldgen gets half of the cores and meshing a quarter, leaving the rest for rendering and the main thread.
+    fn default() -> Self {
+        let cores = available_parallelism().map_or(4, usize::from);
+        Self::new((cores / 2).max(1), (cores / 4).max(1))
+    }
+}
+
+impl ChunkTaskPools {
+    #[must_use]
+    pub fn new(worldgen_threads: usize, mesh_threads: usize) -> Self {
+        Self {
+            worldgen: TaskPoolBuilder::new()
+                .num_threads(worldgen_threads)
+                .thread_name("Worldgen".to_string())
+                .build(),
+            mesh: TaskPoolBuilder::new()
+                .num_threads(mesh_threads)
+                .thread_name("Mesher".to_string())
+                .build(),
+        }
+    }
+}
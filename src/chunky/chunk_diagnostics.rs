@@ -0,0 +1,239 @@
+//! Timing diagnostics for worldgen, meshing and chunk baking.
+//! Shown in the debug overlay and recordable to a CSV file with the `diagnostics` console command.
+
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+    time::Duration,
+};
+
+use anyhow::{Context, Result, anyhow, bail};
+use bevy::{
+    diagnostic::{
+        Diagnostic, DiagnosticPath, Diagnostics, DiagnosticsStore, FrameTimeDiagnosticsPlugin,
+        RegisterDiagnostic,
+    },
+    prelude::*,
+};
+
+use crate::{
+    console::commands::{AddConsoleCommand, ConsoleCommand},
+    render::chunk_material::take_bake_time,
+};
+
+/// How often a row is appended to the CSV file while recording.
+pub const RECORD_INTERVAL: Duration = Duration::from_secs(1);
+
+pub struct ChunkDiagnosticsPlugin;
+
+impl ChunkDiagnosticsPlugin {
+    /// Average time spent in `WorldGenerator::generate` per chunk.
+    pub const WORLDGEN_TIME: DiagnosticPath = DiagnosticPath::const_new("chunk/worldgen_time");
+    /// Average time spent in `build_chunk_instance_data` per chunk.
+    pub const MESH_TIME: DiagnosticPath = DiagnosticPath::const_new("chunk/mesh_time");
+    /// Average time a chunk waits in the load queue before its worldgen task starts.
+    pub const WORLDGEN_QUEUE_WAIT: DiagnosticPath =
+        DiagnosticPath::const_new("chunk/worldgen_queue_wait");
+    /// Average time a chunk waits in the mesh queue before its mesh task starts.
+    pub const MESH_QUEUE_WAIT: DiagnosticPath = DiagnosticPath::const_new("chunk/mesh_queue_wait");
+    pub const CHUNKS_GENERATED_PER_SECOND: DiagnosticPath =
+        DiagnosticPath::const_new("chunk/generated_per_second");
+    pub const CHUNKS_MESHED_PER_SECOND: DiagnosticPath =
+        DiagnosticPath::const_new("chunk/meshed_per_second");
+    /// Average time spent uploading a chunk's quads to the GPU in `ChunkMaterial::bake`.
+    pub const BAKE_TIME: DiagnosticPath = DiagnosticPath::const_new("chunk/bake_time");
+
+    /// Every diagnostic registered by this plugin, in overlay and CSV column order.
+    pub const PATHS: [DiagnosticPath; 7] = [
+        Self::WORLDGEN_TIME,
+        Self::MESH_TIME,
+        Self::WORLDGEN_QUEUE_WAIT,
+        Self::MESH_QUEUE_WAIT,
+        Self::CHUNKS_GENERATED_PER_SECOND,
+        Self::CHUNKS_MESHED_PER_SECOND,
+        Self::BAKE_TIME,
+    ];
+
+    /// Records the mean of `durations` in milliseconds.
+    /// Frames without any durations are skipped so that idle frames don't drag the average to 0.
+    pub fn measure_durations(
+        diagnostics: &mut Diagnostics,
+        path: &DiagnosticPath,
+        durations: &[Duration],
+    ) {
+        if durations.is_empty() {
+            return;
+        }
+        diagnostics.add_measurement(path, || {
+            let total: Duration = durations.iter().sum();
+            total.as_secs_f64() * 1000. / durations.len() as f64
+        });
+    }
+
+    /// Records `count` events happening this frame as a per-second rate.
+    pub fn measure_rate(diagnostics: &mut Diagnostics, path: &DiagnosticPath, count: usize, time: &Time) {
+        let delta = time.delta_secs_f64();
+        if delta <= 0. {
+            return;
+        }
+        diagnostics.add_measurement(path, || count as f64 / delta);
+    }
+}
+
+impl Plugin for ChunkDiagnosticsPlugin {
+    fn build(&self, app: &mut App) {
+        for path in [
+            Self::WORLDGEN_TIME,
+            Self::MESH_TIME,
+            Self::WORLDGEN_QUEUE_WAIT,
+            Self::MESH_QUEUE_WAIT,
+            Self::BAKE_TIME,
+        ] {
+            app.register_diagnostic(Diagnostic::new(path).with_suffix(" ms"));
+        }
+        for path in [
+            Self::CHUNKS_GENERATED_PER_SECOND,
+            Self::CHUNKS_MESHED_PER_SECOND,
+        ] {
+            app.register_diagnostic(Diagnostic::new(path).with_suffix("/s"));
+        }
+
+        app.init_resource::<DiagnosticsRecorder>();
+        app.add_systems(Update, (measure_bake_time, record_diagnostics));
+        app.add_console_command(DiagnosticsCommand);
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn measure_bake_time(mut diagnostics: Diagnostics) {
+    let (total, count) = take_bake_time();
+    if count == 0 {
+        return;
+    }
+    diagnostics.add_measurement(&ChunkDiagnosticsPlugin::BAKE_TIME, || {
+        total.as_secs_f64() * 1000. / count as f64
+    });
+}
+
+/// Appends the averaged diagnostics to a CSV file every [`RECORD_INTERVAL`].
+#[derive(Resource)]
+pub struct DiagnosticsRecorder {
+    file: Option<(PathBuf, BufWriter<File>)>,
+    timer: Timer,
+}
+
+impl Default for DiagnosticsRecorder {
+    fn default() -> Self {
+        Self {
+            file: None,
+            timer: Timer::new(RECORD_INTERVAL, TimerMode::Repeating),
+        }
+    }
+}
+
+impl DiagnosticsRecorder {
+    /// Starts recording into a new CSV file at `path`, overwriting it if it exists.
+    ///
+    /// # Errors
+    /// If the file can not be created.
+    pub fn start(&mut self, path: PathBuf) -> Result<()> {
+        let mut writer = BufWriter::new(
+            File::create(&path).with_context(|| format!("Could not create {}", path.display()))?,
+        );
+        let mut header = vec!["elapsed_seconds".to_string(), "fps".to_string(), "frame_time".to_string()];
+        header.extend(ChunkDiagnosticsPlugin::PATHS.iter().map(ToString::to_string));
+        writeln!(writer, "{}", header.join(","))?;
+
+        self.file = Some((path, writer));
+        self.timer.reset();
+        Ok(())
+    }
+
+    /// Stops recording and returns the path of the finished file.
+    ///
+    /// # Errors
+    /// If nothing is being recorded or the file could not be flushed.
+    pub fn stop(&mut self) -> Result<PathBuf> {
+        let (path, mut writer) = self.file.take().ok_or_else(|| anyhow!("Not recording."))?;
+        writer.flush()?;
+        Ok(path)
+    }
+
+    #[must_use]
+    pub const fn is_recording(&self) -> bool {
+        self.file.is_some()
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn record_diagnostics(
+    mut recorder: ResMut<DiagnosticsRecorder>,
+    diagnostics: Res<DiagnosticsStore>,
+    time: Res<Time>,
+) {
+    if !recorder.is_recording() || !recorder.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    let average = |path: &DiagnosticPath| {
+        diagnostics
+            .get(path)
+            .and_then(Diagnostic::average)
+            .map_or_else(String::new, |value| format!("{value:.3}"))
+    };
+    let mut row = vec![
+        format!("{:.3}", time.elapsed_secs_f64()),
+        average(&FrameTimeDiagnosticsPlugin::FPS),
+        average(&FrameTimeDiagnosticsPlugin::FRAME_TIME),
+    ];
+    row.extend(ChunkDiagnosticsPlugin::PATHS.iter().map(average));
+
+    let Some((_, writer)) = &mut recorder.file else {
+        return;
+    };
+    if let Err(error) = writeln!(writer, "{}", row.join(",")) {
+        if let Some((path, _)) = recorder.file.take() {
+            error!("Could not write diagnostics to {}: {error}", path.display());
+        }
+    }
+}
+
+/// `diagnostics record <file.csv>` or `diagnostics stop`.
+struct DiagnosticsCommand;
+
+impl ConsoleCommand for DiagnosticsCommand {
+    fn name(&self) -> &'static str {
+        "diagnostics"
+    }
+
+    fn usage(&self) -> &'static str {
+        "diagnostics record <file.csv> | diagnostics stop"
+    }
+
+    fn run(&self, args: &[&str], world: &mut World) -> Result<String> {
+        let mut recorder = world.resource_mut::<DiagnosticsRecorder>();
+        match args {
+            ["record", path] => {
+                if recorder.is_recording() {
+                    recorder.stop()?;
+                }
+                recorder.start(PathBuf::from(path))?;
+                Ok(format!("Recording chunk diagnostics to {path}"))
+            }
+            ["stop"] => {
+                let path = recorder.stop()?;
+                Ok(format!("Saved chunk diagnostics to {}", path.display()))
+            }
+            _ => bail!("Usage: {}", self.usage()),
+        }
+    }
+
+    fn complete(&self, args: &[&str], _world: &World) -> Vec<String> {
+        if args.is_empty() {
+            vec!["record".to_string(), "stop".to_string()]
+        } else {
+            vec![]
+        }
+    }
+}
@@ -0,0 +1,80 @@
+//! Counts how many scanners and tickets want each chunk, so that a chunk is only unloaded once nobody wants it.
+
+use bevy::{platform::collections::HashMap, prelude::*};
+
+use crate::position::ChunkPosition;
+
+#[derive(Resource, Default)]
+pub struct ChunkInterest {
+    data: HashMap<ChunkPosition, u32>,
+    mesh: HashMap<ChunkPosition, u32>,
+    simulation: HashMap<ChunkPosition, u32>,
+}
+
+impl ChunkInterest {
+    /// Adds one holder to a chunk's data. Every call must be matched by a [`ChunkInterest::release_data`].
+    pub fn hold_data(&mut self, chunk_position: ChunkPosition) {
+        *self.data.entry(chunk_position).or_default() += 1;
+    }
+
+    /// Removes one holder from a chunk's data. Returns true if nothing holds it anymore.
+    pub fn release_data(&mut self, chunk_position: ChunkPosition) -> bool {
+        release(&mut self.data, chunk_position)
+    }
+
+    pub fn hold_mesh(&mut self, chunk_position: ChunkPosition) {
+        *self.mesh.entry(chunk_position).or_default() += 1;
+    }
+
+    /// Removes one holder from a chunk's mesh. Returns true if nothing holds it anymore.
+    pub fn release_mesh(&mut self, chunk_position: ChunkPosition) -> bool {
+        release(&mut self.mesh, chunk_position)
+    }
+
+    /// Adds one holder to a chunk's simulation. Simulated chunks keep running while nobody is near them.
+    pub fn hold_simulation(&mut self, chunk_position: ChunkPosition) {
+        *self.simulation.entry(chunk_position).or_default() += 1;
+    }
+
+    pub fn release_simulation(&mut self, chunk_position: ChunkPosition) -> bool {
+        release(&mut self.simulation, chunk_position)
+    }
+
+    #[must_use]
+    pub fn is_data_held(&self, chunk_position: ChunkPosition) -> bool {
+        self.data.contains_key(&chunk_position)
+    }
+
+    #[must_use]
+    pub fn is_mesh_held(&self, chunk_position: ChunkPosition) -> bool {
+        self.mesh.contains_key(&chunk_position)
+    }
+
+    #[must_use]
+    pub fn is_simulated(&self, chunk_position: ChunkPosition) -> bool {
+        self.simulation.contains_key(&chunk_position) || self.mesh.contains_key(&chunk_position)
+    }
+
+    /// Every chunk that should be simulated: chunks held for simulation, and the chunks scanners show.
+    pub fn simulated(&self) -> impl Iterator<Item = ChunkPosition> + '_ {
+        self.simulation.keys().copied().chain(
+            self.mesh
+                .keys()
+                .copied()
+                .filter(|chunk_position| !self.simulation.contains_key(chunk_position)),
+        )
+    }
+}
+
+fn release(counts: &mut HashMap<ChunkPosition, u32>, chunk_position: ChunkPosition) -> bool {
+    let Some(count) = counts.get_mut(&chunk_position) else {
+        warn!("Released chunk {chunk_position:?} that was not held.");
+        return true;
+    };
+    *count -= 1;
+    if *count == 0 {
+        counts.remove(&chunk_position);
+        return true;
+    }
+    false
+}
@@ -0,0 +1,100 @@
+//! Decides which queued chunks are generated and meshed first.
+//! Chunks that are visible, or that a scanner is about to fly into, jump ahead of the ones behind it.
+
+use bevy::{
+    prelude::*,
+    render::primitives::{Frustum, Sphere},
+};
+
+use crate::position::{ChunkPosition, FloatingPosition};
+
+use super::chunk::CHUNK_SIZE_F32;
+
+/// How many seconds ahead the scanner's position is predicted from its velocity.
+pub const PRIORITY_LOOKAHEAD_SECONDS: f32 = 2.;
+
+/// Chunks inside the view frustum count as this many times closer.
+pub const VISIBLE_PRIORITY_FACTOR: f32 = 4.;
+
+/// Queues are only reordered when the view direction turns further than this (as a cosine).
+const REPRIORITIZE_VIEW_COSINE: f32 = 0.9;
+
+/// Where the chunk loader is focused. Each chunk is prioritised by the scanner that wants it the most.
+#[derive(Clone, Default)]
+pub struct LoadFocus(pub Vec<ScannerView>);
+
+impl LoadFocus {
+    /// Lower values load first.
+    #[must_use]
+    pub fn priority(&self, chunk_position: ChunkPosition) -> i64 {
+        self.0
+            .iter()
+            .map(|view| view.priority(chunk_position))
+            .min()
+            .unwrap_or_default()
+    }
+
+    /// True if queued chunks should be reordered for this focus.
+    /// Small changes are ignored because reordering is linear in the queue size.
+    #[must_use]
+    pub fn differs_from(&self, other: &Self) -> bool {
+        self.0.len() != other.0.len()
+            || self.0.iter().zip(&other.0).any(|(a, b)| a.differs_from(b))
+    }
+}
+
+/// Where a scanner is, where it's looking and where it's going.
+#[derive(Clone, Default)]
+pub struct ScannerView {
+    pub center: ChunkPosition,
+    /// the position the scanner will reach in [`PRIORITY_LOOKAHEAD_SECONDS`], in blocks.
+    pub predicted: FloatingPosition,
+    pub forward: Vec3,
+    pub frustum: Option<Frustum>,
+}
+
+impl ScannerView {
+    #[must_use]
+    pub fn new(transform: &GlobalTransform, velocity: Vec3, frustum: Option<&Frustum>) -> Self {
+        let position = FloatingPosition(transform.translation());
+        Self {
+            center: position.into(),
+            predicted: FloatingPosition(position.0 + velocity * PRIORITY_LOOKAHEAD_SECONDS),
+            forward: transform.forward().into(),
+            frustum: frustum.cloned(),
+        }
+    }
+
+    #[must_use]
+    pub fn priority(&self, chunk_position: ChunkPosition) -> i64 {
+        let chunk_center =
+            FloatingPosition::from(chunk_position).0 + Vec3::splat(CHUNK_SIZE_F32 / 2.);
+        let current_center =
+            FloatingPosition::from(self.center).0 + Vec3::splat(CHUNK_SIZE_F32 / 2.);
+
+        // measured in chunks, so that the integer priorities keep enough precision
+        let mut distance_squared = (chunk_center.distance_squared(current_center))
+            .min(chunk_center.distance_squared(self.predicted.0))
+            / (CHUNK_SIZE_F32 * CHUNK_SIZE_F32);
+
+        let is_visible = self.frustum.as_ref().is_some_and(|frustum| {
+            let bounds = Sphere {
+                center: chunk_center.into(),
+                radius: CHUNK_SIZE_F32 * 3_f32.sqrt() / 2.,
+            };
+            frustum.intersects_sphere(&bounds, false)
+        });
+        if is_visible {
+            distance_squared /= VISIBLE_PRIORITY_FACTOR * VISIBLE_PRIORITY_FACTOR;
+        }
+
+        (distance_squared * 16.) as i64
+    }
+
+    #[must_use]
+    pub fn differs_from(&self, other: &Self) -> bool {
+        self.center != other.center
+            || ChunkPosition::from(self.predicted) != ChunkPosition::from(other.predicted)
+            || self.forward.dot(other.forward) < REPRIORITIZE_VIEW_COSINE
+    }
+}
@@ -0,0 +1,181 @@
+//! A priority queue of chunk positions with O(1) membership checks and removal.
+
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::BinaryHeap,
+    time::Instant,
+};
+
+use bevy::platform::collections::HashMap;
+
+use crate::position::ChunkPosition;
+
+/// Pops the chunk with the lowest priority value first.
+/// Priorities are fixed when a chunk is pushed. Call [`ChunkQueue::reprioritize`] when
+/// whatever they were computed from (e.g. the player's position) changes.
+#[derive(Default)]
+pub struct ChunkQueue {
+    // when each queued chunk was pushed, and the generation of its live heap entry.
+    // this is the source of truth for what is queued.
+    members: HashMap<ChunkPosition, (Instant, u64)>,
+    // may contain removed or stale entries, which are skipped when popped.
+    heap: BinaryHeap<Reverse<QueuedChunk>>,
+    // handed to every heap entry, so that an entry left over from before a chunk was removed and pushed again
+    // doesn't pop it at its old priority.
+    next_generation: u64,
+}
+
+struct QueuedChunk {
+    priority: i64,
+    position: ChunkPosition,
+    generation: u64,
+}
+
+impl PartialEq for QueuedChunk {
+    fn eq(&self, other: &Self) -> bool {
+        self.priority == other.priority
+    }
+}
+
+impl Eq for QueuedChunk {}
+
+impl PartialOrd for QueuedChunk {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for QueuedChunk {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.priority.cmp(&other.priority)
+    }
+}
+
+impl ChunkQueue {
+    /// Queues a chunk. Does nothing if it is already queued.
+    pub fn push(&mut self, position: ChunkPosition, priority: i64) {
+        if self.members.contains_key(&position) {
+            return;
+        }
+        let generation = self.next_generation;
+        self.next_generation += 1;
+        self.members.insert(position, (Instant::now(), generation));
+        self.heap.push(Reverse(QueuedChunk {
+            priority,
+            position,
+            generation,
+        }));
+    }
+
+    /// Removes the chunk with the lowest priority value, and returns it along with when it was queued.
+    pub fn pop(&mut self) -> Option<(ChunkPosition, Instant)> {
+        while let Some(Reverse(queued)) = self.heap.pop() {
+            if self.is_live(&queued) {
+                let (queued_at, _) = self.members.remove(&queued.position)?;
+                return Some((queued.position, queued_at));
+            }
+        }
+        None
+    }
+
+    /// Returns true if the chunk was queued.
+    pub fn remove(&mut self, position: &ChunkPosition) -> bool {
+        let removed = self.members.remove(position).is_some();
+        // removed entries stay in the heap until popped. rebuild before they pile up.
+        if removed && self.heap.len() > self.members.len() * 2 + 64 {
+            let heap = std::mem::take(&mut self.heap);
+            self.heap = heap
+                .into_iter()
+                .filter(|Reverse(queued)| self.is_live(queued))
+                .collect();
+        }
+        removed
+    }
+
+    fn is_live(&self, queued: &QueuedChunk) -> bool {
+        self.members
+            .get(&queued.position)
+            .is_some_and(|&(_, generation)| generation == queued.generation)
+    }
+
+    #[must_use]
+    pub fn contains(&self, position: &ChunkPosition) -> bool {
+        self.members.contains_key(position)
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &ChunkPosition> {
+        self.members.keys()
+    }
+
+    /// Recomputes the priority of every queued chunk. O(n).
+    pub fn reprioritize(&mut self, mut priority: impl FnMut(ChunkPosition) -> i64) {
+        self.heap = self
+            .members
+            .iter()
+            .map(|(&position, &(_, generation))| {
+                Reverse(QueuedChunk {
+                    priority: priority(position),
+                    position,
+                    generation,
+                })
+            })
+            .collect();
+    }
+}
+
+#[test]
+fn pops_in_priority_order() {
+    let mut queue = ChunkQueue::default();
+    queue.push(ChunkPosition::new(3, 0, 0), 9);
+    queue.push(ChunkPosition::new(1, 0, 0), 1);
+    queue.push(ChunkPosition::new(2, 0, 0), 4);
+    queue.push(ChunkPosition::new(1, 0, 0), 0);
+    assert!(queue.remove(&ChunkPosition::new(2, 0, 0)));
+
+    assert_eq!(queue.len(), 2);
+    assert_eq!(
+        queue.pop().map(|(position, _)| position),
+        Some(ChunkPosition::new(1, 0, 0))
+    );
+
+    queue.reprioritize(|position| -i64::from(position.x));
+    queue.push(ChunkPosition::new(4, 0, 0), 0);
+    assert_eq!(
+        queue.pop().map(|(position, _)| position),
+        Some(ChunkPosition::new(3, 0, 0))
+    );
+    assert_eq!(
+        queue.pop().map(|(position, _)| position),
+        Some(ChunkPosition::new(4, 0, 0))
+    );
+    assert!(queue.pop().is_none());
+}
+
+#[test]
+fn pushed_again_after_removal_pops_at_the_new_priority() {
+    let mut queue = ChunkQueue::default();
+    queue.push(ChunkPosition::new(1, 0, 0), 5);
+    queue.push(ChunkPosition::new(2, 0, 0), 7);
+    assert!(queue.remove(&ChunkPosition::new(1, 0, 0)));
+    queue.push(ChunkPosition::new(1, 0, 0), 9);
+
+    assert_eq!(
+        queue.pop().map(|(position, _)| position),
+        Some(ChunkPosition::new(2, 0, 0))
+    );
+    assert_eq!(
+        queue.pop().map(|(position, _)| position),
+        Some(ChunkPosition::new(1, 0, 0))
+    );
+    assert!(queue.pop().is_none());
+}
@@ -0,0 +1,105 @@
+//! Tracks how far along generation each chunk is.
+//! Stages that read across chunk borders (trees, meshing) may only run once
+//! all 26 neighbours have reached the previous stage, the same way meshing needs a full `ChunkRefs`.
+
+use bevy::{platform::collections::HashMap, prelude::*};
+
+use crate::{position::ChunkPosition, utils::index_to_ivec3_bounds};
+
+/// The lifecycle of a chunk, in order. A chunk only moves forwards, except when it is unloaded
+/// (back to `Empty`) or its data changes after meshing (back to `Decorated`).
+/// There is no light stage yet. It goes between `Decorated` and `Meshed` once light is propagated.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ChunkStatus {
+    /// Not loaded, or still generating.
+    #[default]
+    Empty,
+    /// Terrain has been generated from noise. Only depends on the chunk itself.
+    Terrain,
+    /// Features that may cross chunk borders have been placed.
+    Decorated,
+    /// The mesh matches the data. Includes chunks without any visible faces.
+    Meshed,
+}
+
+impl ChunkStatus {
+    /// How many rings of chunks around a meshed chunk must be generated, one per stage that needs its neighbours.
+    pub const GENERATION_MARGIN: u32 = 2;
+
+    /// The status every neighbour must have reached before a chunk can advance to `self`.
+    #[must_use]
+    pub const fn required_neighbour_status(self) -> Option<Self> {
+        match self {
+            Self::Empty | Self::Terrain => None,
+            Self::Decorated => Some(Self::Terrain),
+            Self::Meshed => Some(Self::Decorated),
+        }
+    }
+
+    #[must_use]
+    pub const fn next(self) -> Option<Self> {
+        match self {
+            Self::Empty => Some(Self::Terrain),
+            Self::Terrain => Some(Self::Decorated),
+            Self::Decorated => Some(Self::Meshed),
+            Self::Meshed => None,
+        }
+    }
+}
+
+/// The status of every chunk that is not `Empty`.
+#[derive(Default)]
+pub struct ChunkStatuses {
+    statuses: HashMap<ChunkPosition, ChunkStatus>,
+    // chunks whose status changed since the last `take_changed`
+    changed: Vec<ChunkPosition>,
+}
+
+impl ChunkStatuses {
+    #[must_use]
+    pub fn get(&self, chunk_position: ChunkPosition) -> ChunkStatus {
+        self.statuses
+            .get(&chunk_position)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn set(&mut self, chunk_position: ChunkPosition, status: ChunkStatus) {
+        let previous = if status == ChunkStatus::Empty {
+            self.statuses.remove(&chunk_position)
+        } else {
+            self.statuses.insert(chunk_position, status)
+        };
+        if previous.unwrap_or_default() != status {
+            self.changed.push(chunk_position);
+        }
+    }
+
+    /// Moves a chunk back to `status` if it is further along.
+    pub fn downgrade(&mut self, chunk_position: ChunkPosition, status: ChunkStatus) {
+        if self.get(chunk_position) > status {
+            self.set(chunk_position, status);
+        }
+    }
+
+    /// True if the chunk and all 26 of its neighbours are at `status` or further.
+    #[must_use]
+    pub fn neighbourhood_reached(&self, center: ChunkPosition, status: ChunkStatus) -> bool {
+        (0..27).all(|i| {
+            let offset = ChunkPosition(index_to_ivec3_bounds(i, 3) + IVec3::NEG_ONE);
+            self.get(center + offset) >= status
+        })
+    }
+
+    /// True if the chunk's neighbours allow it to advance to `status`.
+    #[must_use]
+    pub fn can_advance_to(&self, chunk_position: ChunkPosition, status: ChunkStatus) -> bool {
+        status
+            .required_neighbour_status()
+            .is_none_or(|required| self.neighbourhood_reached(chunk_position, required))
+    }
+
+    pub fn take_changed(&mut self) -> Vec<ChunkPosition> {
+        std::mem::take(&mut self.changed)
+    }
+}
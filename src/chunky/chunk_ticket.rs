@@ -0,0 +1,178 @@
+//! Chunk tickets keep an area of chunks loaded without any scanner nearby, e.g. so that factories keep running.
+//! Spawn an entity with a [`ChunkTicket`] from Rust, or declare a `chunk-ticket` prototype from a mod:
+//! ```lua
+//! extend {
+//!     type = "chunk-ticket",
+//!     name = "spawn-area",
+//!     position = {0, 0, 0},
+//!     radius = 2,
+//!     level = "simulation"
+//! }
+//! ```
+//! Every fixed update a [`ChunkTick`] is sent for each loaded chunk that is simulated, which is what anything
+//! running inside chunks should advance on.
+
+use bevy::{
+    ecs::{component::HookContext, world::DeferredWorld},
+    prelude::*,
+};
+
+use crate::{
+    mod_manager::prototypes::{ChunkTicketPrototypes, Prototypes},
+    position::ChunkPosition,
+};
+
+use super::{
+    async_chunkloader::{AsyncChunkloader, Chunks},
+    chunk_interest::ChunkInterest,
+};
+
+pub struct ChunkTicketPlugin;
+
+impl Plugin for ChunkTicketPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ChunkTickCount>()
+            .add_event::<ChunkTick>()
+            .add_systems(FixedUpdate, tick_simulated_chunks)
+            .add_systems(
+                Update,
+                spawn_prototype_tickets.run_if(resource_added::<ChunkTicketPrototypes>),
+            );
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TicketLevel {
+    /// The chunk data stays loaded, but nothing in it runs.
+    Data,
+    /// The chunk data stays loaded and the chunk is simulated.
+    Simulation,
+}
+
+/// Keeps the cube of chunks within `radius` of `center` loaded. Tickets never mesh chunks.
+/// Tickets are immutable, insert a new one to move or resize it.
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug)]
+#[component(immutable, on_insert = hold_ticket, on_replace = release_ticket)]
+pub struct ChunkTicket {
+    pub center: ChunkPosition,
+    pub radius: u32,
+    pub level: TicketLevel,
+}
+
+impl ChunkTicket {
+    #[must_use]
+    pub const fn new(center: ChunkPosition, radius: u32, level: TicketLevel) -> Self {
+        Self {
+            center,
+            radius,
+            level,
+        }
+    }
+
+    pub fn chunk_positions(&self) -> impl Iterator<Item = ChunkPosition> + use<> {
+        let center = self.center;
+        let radius = self.radius as i32;
+        (-radius..=radius).flat_map(move |z| {
+            (-radius..=radius).flat_map(move |y| {
+                (-radius..=radius).map(move |x| center + ChunkPosition::new(x, y, z))
+            })
+        })
+    }
+}
+
+/// Sent every fixed update for each loaded chunk that is held by a simulation ticket or shown by a scanner.
+#[derive(Event, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ChunkTick {
+    pub chunk_position: ChunkPosition,
+    /// counts up by one every fixed update
+    pub tick: u64,
+}
+
+/// How many fixed updates chunks have been ticked for.
+#[derive(Resource, Default, Clone, Copy, Debug)]
+pub struct ChunkTickCount(pub u64);
+
+#[allow(clippy::needless_pass_by_value)]
+fn tick_simulated_chunks(
+    mut count: ResMut<ChunkTickCount>,
+    interest: Res<ChunkInterest>,
+    chunks: Res<Chunks>,
+    mut ticks: EventWriter<ChunkTick>,
+) {
+    count.0 += 1;
+    let tick = count.0;
+    ticks.write_batch(
+        interest
+            .simulated()
+            .filter(|chunk_position| chunks.0.contains_key(chunk_position))
+            .map(|chunk_position| ChunkTick {
+                chunk_position,
+                tick,
+            }),
+    );
+}
+
+fn hold_ticket(mut world: DeferredWorld, context: HookContext) {
+    let Some(&ticket) = world.get::<ChunkTicket>(context.entity) else {
+        return;
+    };
+
+    let mut interest = world.resource_mut::<ChunkInterest>();
+    for chunk_position in ticket.chunk_positions() {
+        interest.hold_data(chunk_position);
+        if ticket.level == TicketLevel::Simulation {
+            interest.hold_simulation(chunk_position);
+        }
+    }
+
+    let to_load: Vec<ChunkPosition> = {
+        let chunks = world.resource::<Chunks>();
+        ticket
+            .chunk_positions()
+            .filter(|chunk_position| !chunks.0.contains_key(chunk_position))
+            .collect()
+    };
+    let mut chunkloader = world.resource_mut::<AsyncChunkloader>();
+    for chunk_position in ticket.chunk_positions() {
+        // it may have been about to unload
+        chunkloader.unload_chunk_queue.remove(&chunk_position);
+    }
+    for chunk_position in to_load {
+        if !chunkloader.worldgen_tasks.contains(chunk_position) {
+            chunkloader.queue_chunk_load(chunk_position);
+        }
+    }
+}
+
+fn release_ticket(mut world: DeferredWorld, context: HookContext) {
+    let Some(&ticket) = world.get::<ChunkTicket>(context.entity) else {
+        return;
+    };
+
+    let mut interest = world.resource_mut::<ChunkInterest>();
+    let to_unload: Vec<ChunkPosition> = ticket
+        .chunk_positions()
+        .filter(|&chunk_position| {
+            if ticket.level == TicketLevel::Simulation {
+                interest.release_simulation(chunk_position);
+            }
+            interest.release_data(chunk_position)
+        })
+        .collect();
+
+    let mut chunkloader = world.resource_mut::<AsyncChunkloader>();
+    for chunk_position in to_unload {
+        chunkloader.cancel_chunk_load(chunk_position);
+        chunkloader.unload_chunk_queue.insert(chunk_position);
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn spawn_prototype_tickets(mut commands: Commands, prototypes: Res<ChunkTicketPrototypes>) {
+    for (name, prototype) in prototypes.iter() {
+        commands.spawn((
+            Name::new(format!("Chunk ticket {name}")),
+            ChunkTicket::new(prototype.position, prototype.radius, prototype.level),
+        ));
+    }
+}
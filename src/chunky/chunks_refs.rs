@@ -1,6 +1,6 @@
 use std::{hash::Hash, sync::Arc};
 
-use bevy::prelude::*;
+use bevy::{platform::collections::HashMap, prelude::*};
 
 use crate::{
     mod_manager::prototypes::BlockPrototype,
@@ -27,10 +27,19 @@ impl ChunkRefs {
     /// if `ChunkData` doesn't exist in input `world_data`
     #[must_use]
     pub fn try_new(chunks: &Chunks, center_chunk_position: ChunkPosition) -> Option<Self> {
+        Self::try_from_map(&chunks.0, center_chunk_position)
+    }
+
+    /// Like [`ChunkRefs::try_new`], for chunk data that isn't stored in [`Chunks`].
+    #[must_use]
+    pub fn try_from_map(
+        chunks: &HashMap<ChunkPosition, Arc<ChunkData>>,
+        center_chunk_position: ChunkPosition,
+    ) -> Option<Self> {
         let get_chunk = |i| {
             //let offset = ADJACENT_CHUNK_DIRECTIONS[i] + IVec3::NEG_ONE;
             let offset = ChunkPosition(index_to_ivec3_bounds(i, 3) + IVec3::NEG_ONE);
-            chunks.0.get(&(center_chunk_position + offset))
+            chunks.get(&(center_chunk_position + offset))
         };
         #[rustfmt::skip]
         let adjacent_chunks: [Arc<ChunkData>; 27] = [
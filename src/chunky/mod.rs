@@ -1,8 +1,16 @@
 pub mod async_chunkloader;
+pub mod chunk_budget;
 pub mod chunk;
+pub mod chunk_diagnostics;
+pub mod chunk_interest;
+pub mod chunk_priority;
+pub mod chunk_queue;
+pub mod chunk_status;
+pub mod chunk_ticket;
 pub mod chunks_refs;
 pub mod constants;
 pub mod face_direction;
 pub mod greedy_mesher_optimized;
 pub mod lod;
 pub mod quad;
+pub mod task_channel;
@@ -0,0 +1,127 @@
+//! Runs chunk tasks in the background and collects their results through a channel,
+//! so that the main thread doesn't have to poll every task each frame.
+
+use std::{
+    sync::{
+        Mutex,
+        mpsc::{Receiver, Sender, channel},
+    },
+    time::{Duration, Instant},
+};
+
+use bevy::{platform::collections::HashMap, tasks::TaskPool};
+
+use crate::position::ChunkPosition;
+
+/// A finished task.
+pub struct TaskResult<T> {
+    pub position: ChunkPosition,
+    generation: u64,
+    pub value: T,
+    /// How long the task itself ran, not counting time spent waiting in the pool.
+    pub duration: Duration,
+}
+
+/// Background tasks keyed by chunk position.
+/// Tasks are detached, so cancelling one doesn't stop it. Instead every task is tagged with a generation,
+/// and results whose generation is no longer the pending one for their chunk are thrown away.
+pub struct TaskChannel<T> {
+    sender: Sender<TaskResult<T>>,
+    // only ever locked by the main thread. the mutex makes the channel `Sync` so it can live in a resource.
+    receiver: Mutex<Receiver<TaskResult<T>>>,
+    pending: HashMap<ChunkPosition, u64>,
+    next_generation: u64,
+    // includes cancelled tasks that are still running
+    in_flight: usize,
+}
+
+impl<T> Default for TaskChannel<T> {
+    fn default() -> Self {
+        let (sender, receiver) = channel();
+        Self {
+            sender,
+            receiver: Mutex::new(receiver),
+            pending: HashMap::default(),
+            next_generation: 0,
+            in_flight: 0,
+        }
+    }
+}
+
+impl<T: Send + 'static> TaskChannel<T> {
+    /// Starts a task for a chunk. A task that was already pending for this chunk becomes stale.
+    pub fn spawn(
+        &mut self,
+        task_pool: &TaskPool,
+        position: ChunkPosition,
+        task: impl FnOnce() -> T + Send + 'static,
+    ) {
+        let generation = self.next_generation;
+        self.next_generation += 1;
+        self.pending.insert(position, generation);
+        self.in_flight += 1;
+
+        let sender = self.sender.clone();
+        task_pool
+            .spawn(async move {
+                let start = Instant::now();
+                let value = task();
+                // the receiver only goes away when the app shuts down
+                let _ = sender.send(TaskResult {
+                    position,
+                    generation,
+                    value,
+                    duration: start.elapsed(),
+                });
+            })
+            .detach();
+    }
+
+    /// Marks the pending task for this chunk as stale. Returns false if there was none.
+    pub fn cancel(&mut self, position: ChunkPosition) -> bool {
+        self.pending.remove(&position).is_some()
+    }
+
+    #[must_use]
+    pub fn contains(&self, position: ChunkPosition) -> bool {
+        self.pending.contains_key(&position)
+    }
+
+    /// How many tasks are pending, not counting cancelled ones.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// How many tasks are still running, including cancelled ones. Use this to limit the load on the pool.
+    #[must_use]
+    pub const fn in_flight(&self) -> usize {
+        self.in_flight
+    }
+
+    /// Returns the results of up to `max` tasks that finished since the last call, skipping stale ones.
+    /// The rest are returned by later calls.
+    pub fn drain_finished(&mut self, max: usize) -> Vec<TaskResult<T>> {
+        let receiver = self
+            .receiver
+            .get_mut()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let mut finished = vec![];
+        while finished.len() < max {
+            let Ok(result) = receiver.try_recv() else {
+                break;
+            };
+            self.in_flight -= 1;
+            if self.pending.get(&result.position) == Some(&result.generation) {
+                self.pending.remove(&result.position);
+                finished.push(result);
+            }
+        }
+        finished
+    }
+}
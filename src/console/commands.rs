@@ -0,0 +1,131 @@
+//! Text commands that can be typed into the in-game console.
+//! Any plugin can register its own command with [`AddConsoleCommand::add_console_command`].
+
+use std::{collections::BTreeMap, str::FromStr, sync::Arc};
+
+use anyhow::{Context, Result, anyhow};
+use bevy::prelude::*;
+
+/// A command that can be invoked from the console, e.g. `tp 0 200 0`.
+pub trait ConsoleCommand: Send + Sync + 'static {
+    /// The first word of the command line.
+    fn name(&self) -> &'static str;
+
+    /// Shown by `help`. Should list the arguments, e.g. `tp <x> <y> <z>`.
+    fn usage(&self) -> &'static str;
+
+    /// Runs the command with everything after the command name split on whitespace.
+    /// The returned string is printed to the console log.
+    ///
+    /// # Errors
+    /// If the arguments are malformed or the command could not be applied.
+    fn run(&self, args: &[&str], world: &mut World) -> Result<String>;
+
+    /// Suggestions for the argument currently being typed.
+    /// `args` contains every argument before the one being completed.
+    fn complete(&self, _args: &[&str], _world: &World) -> Vec<String> {
+        vec![]
+    }
+}
+
+/// Every command known to the console, keyed by name.
+#[derive(Resource, Default, Clone)]
+pub struct ConsoleCommands(BTreeMap<&'static str, Arc<dyn ConsoleCommand>>);
+
+impl ConsoleCommands {
+    /// # Panics
+    /// If a command with the same name is already registered.
+    pub fn register(&mut self, command: impl ConsoleCommand) {
+        let name = command.name();
+        assert!(
+            self.0.insert(name, Arc::new(command)).is_none(),
+            "Console command {name} registered twice."
+        );
+    }
+
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<Arc<dyn ConsoleCommand>> {
+        self.0.get(name).cloned()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Arc<dyn ConsoleCommand>> {
+        self.0.values()
+    }
+
+    pub fn names_with_prefix<'a>(&'a self, prefix: &'a str) -> impl Iterator<Item = &'static str> + 'a {
+        self.0
+            .keys()
+            .copied()
+            .filter(move |name| name.starts_with(prefix))
+    }
+}
+
+pub trait AddConsoleCommand {
+    /// Registers a command with the console.
+    /// Can be called from any plugin regardless of plugin order.
+    fn add_console_command(&mut self, command: impl ConsoleCommand) -> &mut Self;
+}
+
+impl AddConsoleCommand for App {
+    fn add_console_command(&mut self, command: impl ConsoleCommand) -> &mut Self {
+        self.world_mut()
+            .get_resource_or_init::<ConsoleCommands>()
+            .register(command);
+        self
+    }
+}
+
+/// Parses and runs a single line of console input.
+///
+/// # Errors
+/// If the command does not exist or fails.
+pub fn execute(line: &str, world: &mut World) -> Result<String> {
+    let mut words = line.split_whitespace();
+    let Some(name) = words.next() else {
+        return Ok(String::new());
+    };
+    let args: Vec<&str> = words.collect();
+
+    let command = world
+        .get_resource::<ConsoleCommands>()
+        .and_then(|commands| commands.get(name))
+        .ok_or_else(|| anyhow!("Unknown command `{name}`. Type `help` for a list of commands."))?;
+
+    command.run(&args, world)
+}
+
+/// Parses the argument at `index`, naming it in the error message if it is missing or malformed.
+///
+/// # Errors
+/// If the argument is missing or cannot be parsed as `T`.
+pub fn parse_arg<T: FromStr>(args: &[&str], index: usize, name: &str) -> Result<T>
+where
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    let arg = args
+        .get(index)
+        .ok_or_else(|| anyhow!("Missing argument <{name}>."))?;
+    arg.parse::<T>()
+        .with_context(|| format!("Could not parse <{name}> from `{arg}`."))
+}
+
+pub(super) struct HelpCommand;
+
+impl ConsoleCommand for HelpCommand {
+    fn name(&self) -> &'static str {
+        "help"
+    }
+
+    fn usage(&self) -> &'static str {
+        "help"
+    }
+
+    fn run(&self, _args: &[&str], world: &mut World) -> Result<String> {
+        let commands = world.resource::<ConsoleCommands>();
+        Ok(commands
+            .iter()
+            .map(|command| command.usage())
+            .collect::<Vec<_>>()
+            .join("\n"))
+    }
+}
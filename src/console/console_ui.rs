@@ -0,0 +1,296 @@
+//! In-game command console. Toggled with [`TOGGLE_CONSOLE_KEY`].
+
+use std::collections::VecDeque;
+
+use bevy::{
+    input::{
+        ButtonState,
+        keyboard::{Key, KeyboardInput},
+    },
+    prelude::*,
+};
+
+use super::commands::{AddConsoleCommand, ConsoleCommands, HelpCommand, execute};
+
+pub const TOGGLE_CONSOLE_KEY: KeyCode = KeyCode::Backquote;
+pub const FONT_SIZE: f32 = 18.;
+pub const FONT_COLOR: Color = Color::WHITE;
+pub const BACKGROUND_COLOR: Color = Color::srgba(0., 0., 0., 0.7);
+pub const MAX_LOG_LINES: usize = 16;
+pub const MAX_HISTORY: usize = 64;
+pub const PROMPT: &str = "> ";
+
+pub struct ConsolePlugin;
+
+impl Plugin for ConsolePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Console>()
+            .init_resource::<ConsoleCommands>()
+            .add_console_command(HelpCommand)
+            .add_systems(Startup, spawn_console)
+            .add_systems(
+                Update,
+                (toggle_console, console_input, run_console, update_console_text).chain(),
+            );
+    }
+}
+
+enum ConsoleRequest {
+    Execute(String),
+    Complete,
+}
+
+#[derive(Resource, Default)]
+pub struct Console {
+    open: bool,
+    input: String,
+    log: VecDeque<String>,
+    history: Vec<String>,
+    /// Index into `history` while scrolling with the arrow keys.
+    history_cursor: Option<usize>,
+    requests: Vec<ConsoleRequest>,
+}
+
+impl Console {
+    /// Other input handlers should ignore the keyboard while this is true.
+    #[must_use]
+    pub const fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn print(&mut self, message: impl Into<String>) {
+        for line in message.into().lines() {
+            if self.log.len() == MAX_LOG_LINES {
+                self.log.pop_front();
+            }
+            self.log.push_back(line.to_string());
+        }
+    }
+
+    fn submit(&mut self) {
+        let line = std::mem::take(&mut self.input);
+        self.history_cursor = None;
+        if line.trim().is_empty() {
+            return;
+        }
+        if self.history.last() != Some(&line) {
+            if self.history.len() == MAX_HISTORY {
+                self.history.remove(0);
+            }
+            self.history.push(line.clone());
+        }
+        self.requests.push(ConsoleRequest::Execute(line));
+    }
+
+    fn history_previous(&mut self) {
+        let cursor = match self.history_cursor {
+            None if self.history.is_empty() => return,
+            None => self.history.len() - 1,
+            Some(cursor) => cursor.saturating_sub(1),
+        };
+        self.history_cursor = Some(cursor);
+        self.input.clone_from(&self.history[cursor]);
+    }
+
+    fn history_next(&mut self) {
+        let Some(cursor) = self.history_cursor else {
+            return;
+        };
+        if cursor + 1 < self.history.len() {
+            self.history_cursor = Some(cursor + 1);
+            self.input.clone_from(&self.history[cursor + 1]);
+        } else {
+            self.history_cursor = None;
+            self.input.clear();
+        }
+    }
+}
+
+#[derive(Component)]
+struct ConsoleRoot;
+
+#[derive(Component)]
+struct ConsoleLogText;
+
+#[derive(Component)]
+struct ConsoleInputText;
+
+fn spawn_console(mut commands: Commands) {
+    let font = TextFont {
+        font_size: FONT_SIZE,
+        ..Default::default()
+    };
+
+    commands
+        .spawn((
+            ConsoleRoot,
+            Node {
+                position_type: PositionType::Absolute,
+                bottom: Val::Px(0.),
+                left: Val::Px(0.),
+                width: Val::Percent(100.),
+                flex_direction: FlexDirection::Column,
+                padding: UiRect::all(Val::Px(8.)),
+                ..default()
+            },
+            BackgroundColor(BACKGROUND_COLOR),
+            Visibility::Hidden,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                ConsoleLogText,
+                Text::default(),
+                font.clone(),
+                TextColor(FONT_COLOR),
+            ));
+            parent.spawn((
+                ConsoleInputText,
+                Text::new(PROMPT),
+                font,
+                TextColor(FONT_COLOR),
+            ));
+        });
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn toggle_console(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut console: ResMut<Console>,
+    mut roots: Query<&mut Visibility, With<ConsoleRoot>>,
+) {
+    if !keys.just_pressed(TOGGLE_CONSOLE_KEY) {
+        return;
+    }
+
+    console.open = !console.open;
+    for mut visibility in &mut roots {
+        *visibility = if console.open {
+            Visibility::Visible
+        } else {
+            Visibility::Hidden
+        };
+    }
+}
+
+fn console_input(mut console: ResMut<Console>, mut keyboard_input: EventReader<KeyboardInput>) {
+    if !console.open {
+        keyboard_input.clear();
+        return;
+    }
+
+    for event in keyboard_input.read() {
+        if event.state != ButtonState::Pressed || event.key_code == TOGGLE_CONSOLE_KEY {
+            continue;
+        }
+
+        match &event.logical_key {
+            Key::Character(characters) => console.input.push_str(characters),
+            Key::Space => console.input.push(' '),
+            Key::Backspace => {
+                console.input.pop();
+            }
+            Key::Enter => console.submit(),
+            Key::Tab => console.requests.push(ConsoleRequest::Complete),
+            Key::ArrowUp => console.history_previous(),
+            Key::ArrowDown => console.history_next(),
+            _ => {}
+        }
+    }
+}
+
+/// Commands need the whole world, so they are run from an exclusive system.
+fn run_console(world: &mut World) {
+    let requests = std::mem::take(&mut world.resource_mut::<Console>().requests);
+
+    for request in requests {
+        match request {
+            ConsoleRequest::Execute(line) => {
+                world.resource_mut::<Console>().print(format!("{PROMPT}{line}"));
+                let output = match execute(&line, world) {
+                    Ok(output) => output,
+                    Err(error) => format!("error: {error:#}"),
+                };
+                world.resource_mut::<Console>().print(output);
+            }
+            ConsoleRequest::Complete => {
+                let input = world.resource::<Console>().input.clone();
+                let (completed, candidates) = complete(&input, world);
+                let mut console = world.resource_mut::<Console>();
+                if candidates.len() > 1 {
+                    console.print(candidates.join("  "));
+                }
+                console.input = completed;
+            }
+        }
+    }
+}
+
+/// Completes the last word of `input`.
+/// Returns the new input line and every candidate that matched.
+fn complete(input: &str, world: &World) -> (String, Vec<String>) {
+    let mut words: Vec<&str> = input.split_whitespace().collect();
+    // a trailing space means a new, empty word is being typed
+    if input.is_empty() || input.ends_with(' ') {
+        words.push("");
+    }
+    let Some((partial, previous)) = words.split_last() else {
+        return (input.to_string(), vec![]);
+    };
+
+    let commands = world.resource::<ConsoleCommands>();
+    let candidates: Vec<String> = match previous.split_first() {
+        None => commands
+            .names_with_prefix(partial)
+            .map(str::to_string)
+            .collect(),
+        Some((name, args)) => commands
+            .get(name)
+            .map(|command| command.complete(args, world))
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|candidate| candidate.starts_with(partial))
+            .collect(),
+    };
+
+    let Some(first) = candidates.first() else {
+        return (input.to_string(), candidates);
+    };
+    let common_prefix = candidates.iter().fold(first.as_str(), |prefix, candidate| {
+        let length = prefix
+            .char_indices()
+            .zip(candidate.chars())
+            .take_while(|((_, a), b)| a == b)
+            .last()
+            .map_or(0, |((i, a), _)| i + a.len_utf8());
+        &prefix[..length]
+    });
+
+    let mut completed = previous.join(" ");
+    if !completed.is_empty() {
+        completed.push(' ');
+    }
+    completed.push_str(common_prefix);
+    if candidates.len() == 1 {
+        completed.push(' ');
+    }
+    (completed, candidates)
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn update_console_text(
+    console: Res<Console>,
+    log_texts: Query<Entity, With<ConsoleLogText>>,
+    input_texts: Query<Entity, With<ConsoleInputText>>,
+    mut writer: TextUiWriter,
+) {
+    if !console.is_changed() {
+        return;
+    }
+
+    for entity in &log_texts {
+        *writer.text(entity, 0) = console.log.iter().cloned().collect::<Vec<_>>().join("\n");
+    }
+    for entity in &input_texts {
+        *writer.text(entity, 0) = format!("{PROMPT}{}_", console.input);
+    }
+}
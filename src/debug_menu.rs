@@ -7,7 +7,7 @@ use bevy::{
 
     use std::time::Duration;
 
-    use crate::{chunky::{async_chunkloader::Chunks, chunk::Chunk}, render::chunk_material::RenderableChunk};
+    use crate::{chunky::{async_chunkloader::Chunks, chunk::Chunk, chunk_diagnostics::ChunkDiagnosticsPlugin}, render::chunk_material::RenderableChunk};
 
 pub const FONT_SIZE: f32 = 32.;
 pub const FONT_COLOR: Color = Color::WHITE;
@@ -90,7 +90,7 @@ fn update(
 
         for entity in query.iter_mut() {
             if let Some((fps, frame_time)) = fps_dialog {
-                *writer.text(entity, 0) = format!("{}{:.0}\n{:.1} ms\nloaded chunks: {}\nmeshed chunks: {}", STRING_FORMAT, fps, frame_time, chunk_entities.0.len(), renderable_chunks.iter().len());
+                *writer.text(entity, 0) = format!("{}{:.0}\n{:.1} ms\nloaded chunks: {}\nmeshed chunks: {}\n{}", STRING_FORMAT, fps, frame_time, chunk_entities.0.len(), renderable_chunks.iter().len(), extract_chunk_diagnostics(&diagnostics));
             } else {
                 *writer.text(entity, 0) = STRING_MISSING.to_string();
             }
@@ -112,6 +112,19 @@ fn extract_fps(diagnostics: &Res<DiagnosticsStore>) -> Option<(f64, f64)> {
     None    
 }
 
+/// One line per chunk pipeline diagnostic, e.g. `chunk/mesh_time: 0.84 ms`
+fn extract_chunk_diagnostics(diagnostics: &Res<DiagnosticsStore>) -> String {
+    ChunkDiagnosticsPlugin::PATHS
+        .iter()
+        .filter_map(|path| diagnostics.get(path))
+        .map(|diagnostic| match diagnostic.average() {
+            Some(average) => format!("{}: {:.2}{}", diagnostic.path(), average, diagnostic.suffix),
+            None => format!("{}: ...", diagnostic.path()),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 fn spawn_text(mut commands: Commands) {
     commands
         .spawn((
@@ -2,6 +2,7 @@
 #![feature(lock_value_accessors)]
 
 pub mod chunky;
+pub mod console;
 pub mod mod_manager;
 pub mod player;
 pub mod position;
@@ -9,4 +10,5 @@ pub mod render;
 pub mod smooth_transform;
 pub mod sun;
 pub mod utils;
+pub mod worldgen;
 pub mod debug_menu;
\ No newline at end of file
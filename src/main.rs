@@ -2,7 +2,6 @@ use std::f32::consts::PI;
 
 use bevy::prelude::*;
 use bevy::{
-    app::TaskPoolThreadAssignmentPolicy,
     core_pipeline::bloom::Bloom,
     pbr::{Atmosphere, AtmosphereSettings},
     render::{
@@ -11,15 +10,20 @@ use bevy::{
     },
 };
 
+use talc::chunky::chunk_diagnostics::ChunkDiagnosticsPlugin;
+use talc::chunky::chunk_ticket::ChunkTicketPlugin;
+use talc::console::console_ui::ConsolePlugin;
 use talc::debug_menu::FpsCounterPlugin;
 use talc::mod_manager::mod_loader::ModLoaderPlugin;
 use talc::player::{
     debug_camera::{FlyCam, NoCameraPlayerPlugin},
     render_distance::Scanner,
     render_distance::ScannerPlugin,
+    spawn::{SpawnOnGround, SpawnPlugin},
 };
 use talc::render::chunk_render_pipeline::ChunkRenderPipelinePlugin;
 use talc::smooth_transform::smooth_transform;
+use talc::worldgen::generator::WorldGenPlugin;
 use talc::{chunky::async_chunkloader::AsyncChunkloaderPlugin, sun::SunPlugin};
 
 fn main() {
@@ -32,28 +36,21 @@ fn main() {
                     ..default()
                 }),
                 ..default()
-            })
-            .set(TaskPoolPlugin {
-                task_pool_options: TaskPoolOptions {
-                    async_compute: TaskPoolThreadAssignmentPolicy {
-                        min_threads: 1,
-                        max_threads: 8,
-                        percent: 0.75,
-                        on_thread_spawn: None,
-                        on_thread_destroy: None,
-                    },
-                    ..default()
-                },
             }),))
         .add_plugins(AsyncChunkloaderPlugin)
         .add_plugins(SunPlugin)
         .add_plugins(ScannerPlugin)
         .add_systems(Startup, setup)
         .add_plugins(ModLoaderPlugin)
+        .add_plugins(WorldGenPlugin)
         .add_plugins(NoCameraPlayerPlugin)
+        .add_plugins(SpawnPlugin)
         .add_systems(Update, smooth_transform)
         .add_plugins(ChunkRenderPipelinePlugin)
         .add_plugins(FpsCounterPlugin)
+        .add_plugins(ChunkDiagnosticsPlugin)
+        .add_plugins(ChunkTicketPlugin)
+        .add_plugins(ConsolePlugin)
         .run();
 }
 
@@ -75,7 +72,9 @@ pub fn setup(
     commands
         .spawn((
             Scanner::new(12),
+            // moved onto the ground once the world generator exists
             Transform::from_xyz(0.0, 200.0, 0.5),
+            SpawnOnGround,
             Camera3d::default(),
             FlyCam,
             Camera {
@@ -1,6 +1,7 @@
 //! Provides conversions from lua tables into various rust types.
 
 use bevy::color::Color;
+use bevy::math::IVec3;
 use mlua::FromLua;
 
 pub(super) struct LuaColor {
@@ -73,3 +74,30 @@ impl From<LuaColor> for Color {
         Self::srgba(value.red, value.green, value.blue, value.alpha)
     }
 }
+
+/// An integer vector, written either as `{x = 1, y = 2, z = 3}` or `{1, 2, 3}`.
+pub(super) struct LuaIVec3(pub IVec3);
+
+impl FromLua for LuaIVec3 {
+    fn from_lua(value: mlua::Value, _lua: &mlua::Lua) -> mlua::Result<Self> {
+        let Some(table) = value.as_table() else {
+            return Err(mlua::Error::ToLuaConversionError {
+                message: Some("Vectors are expected to be a table.".to_string()),
+                to: "Rust IVec3",
+                from: "Lua Value".to_string(),
+            });
+        };
+
+        let component = |name: &str, index: usize| {
+            table
+                .get::<i32>(name)
+                .or_else(|_| table.get::<i32>(index))
+        };
+
+        Ok(Self(IVec3::new(
+            component("x", 1)?,
+            component("y", 2)?,
+            component("z", 3)?,
+        )))
+    }
+}
@@ -1,3 +1,4 @@
 pub mod lua_conversions;
 pub mod mod_loader;
 pub mod prototypes;
+mod require;
@@ -1,19 +1,30 @@
 #![allow(clippy::unwrap_used)]
 
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, BTreeSet},
+    error::Error,
+    fmt::Display,
     fs,
     path::{Path, PathBuf},
 };
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use bevy::prelude::*;
 use mlua::{FromLua, Lua, Table, Value};
+use semver::{Version, VersionReq};
 use serde::Deserialize;
 
 use crate::chunky::chunk::set_block_registry;
 
-use super::prototypes::{BlockPrototypesBuilder, PrototypesBuilder, RawBlockPrototype};
+use super::require;
+
+use super::prototypes::{
+    BiomePrototypes, BiomePrototypesBuilder, BlockPrototypes, BlockPrototypesBuilder,
+    ChunkTicketPrototypes, ChunkTicketPrototypesBuilder, FeaturePrototypes,
+    FeaturePrototypesBuilder, FlatPresetPrototypes, FlatPresetPrototypesBuilder, PrototypesBuilder,
+    RawBiomePrototype, RawBlockPrototype, RawChunkTicketPrototype, RawFeaturePrototype,
+    RawFlatPresetPrototype, RawResourcePrototype, ResourcePrototypes, ResourcePrototypesBuilder,
+};
 
 pub struct ModLoaderPlugin;
 
@@ -27,18 +38,21 @@ impl Plugin for ModLoaderPlugin {
 struct Mod {
     name: String,
     path: PathBuf,
-    //dependancies: Vec<Box<Mod>>,
-    //dependants: Vec<Box<Mod>>
+    version: Version,
+    /// the versions of the game the mod works with
+    talc_version: VersionReq,
+    dependencies: Vec<Dependency>,
 }
 
 impl Mod {
-    fn from_path(path: &Path) -> Self {
+    fn from_path(path: &Path) -> Result<Self, ModLoadError> {
         #[allow(unused)]
         #[derive(Debug, Deserialize)]
         struct ModInfo {
             #[serde(rename = "mod")]
             mod_data: ModData,
-            dependencies: HashMap<String, String>,
+            #[serde(default)]
+            dependencies: BTreeMap<String, String>,
         }
 
         #[allow(unused)]
@@ -55,63 +69,331 @@ impl Mod {
             exclude: Vec<String>,
         }
 
-        let contents = std::fs::read_to_string(path.join("info.toml")).unwrap();
-        let mod_info: ModInfo = toml::from_str(&contents).unwrap();
+        let invalid = |reason: String| ModLoadError::InvalidInfo {
+            path: path.join("info.toml"),
+            reason,
+        };
+        let contents =
+            fs::read_to_string(path.join("info.toml")).map_err(|e| invalid(e.to_string()))?;
+        let mod_info: ModInfo = toml::from_str(&contents).map_err(|e| invalid(e.to_string()))?;
+        let mod_data = mod_info.mod_data;
+        let requirement = |field: &str, requirement: &str| {
+            VersionReq::parse(requirement).map_err(|e| {
+                invalid(format!(
+                    "{field} `{requirement}` is not a version requirement: {e}"
+                ))
+            })
+        };
 
-        Self {
-            name: mod_info.mod_data.name,
+        let version = Version::parse(&mod_data.version).map_err(|e| {
+            invalid(format!(
+                "version `{}` is not a version: {e}",
+                mod_data.version
+            ))
+        })?;
+        let talc_version = requirement("talc_version", &mod_data.talc_version)?;
+        let dependencies = mod_info
+            .dependencies
+            .into_iter()
+            .map(|(key, version)| Ok(Dependency::new(&key, requirement(&key, &version)?)))
+            .collect::<Result<_, ModLoadError>>()?;
+
+        Ok(Self {
+            name: mod_data.name,
             path: path.to_path_buf(),
-        }
+            version,
+            talc_version,
+            dependencies,
+        })
     }
 }
 
-/*
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DependencyKind {
+    /// must be installed, and loads first
+    Required,
+    /// loads first if it is installed
+    Optional,
+    /// must not be installed
+    Incompatible,
+}
+
+/// An entry of the `[dependencies]` table in `info.toml`.
+/// `"?name"` is an optional dependency and `"!name"` an incompatible one, anything else is required.
+/// The value is a version requirement such as `"*"`, `"0.3"` or `">= 0.3"`.
+/// Incompatible dependencies are only incompatible with the versions they match.
 #[derive(Debug)]
-struct ModLoadError {
-    offender: Rc<Mod>,
-    reason: String
+struct Dependency {
+    name: String,
+    kind: DependencyKind,
+    version: VersionReq,
+}
+
+impl Dependency {
+    fn new(key: &str, version: VersionReq) -> Self {
+        let (kind, name) = if let Some(name) = key.strip_prefix('?') {
+            (DependencyKind::Optional, name)
+        } else if let Some(name) = key.strip_prefix('!') {
+            (DependencyKind::Incompatible, name)
+        } else {
+            (DependencyKind::Required, key)
+        };
+        Self {
+            name: name.trim().to_string(),
+            kind,
+            version,
+        }
+    }
+}
+
+/// Why the installed mods can't be loaded.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ModLoadError {
+    /// `info.toml` could not be read or parsed
+    InvalidInfo {
+        path: PathBuf,
+        reason: String,
+    },
+    /// two installed mods have the same name
+    Duplicate {
+        name: String,
+    },
+    MissingDependency {
+        offender: String,
+        dependency: String,
+    },
+    /// a dependency is installed, but not in a version the mod accepts
+    DependencyVersion {
+        offender: String,
+        dependency: String,
+        required: VersionReq,
+        installed: Version,
+    },
+    /// the mod was made for another version of the game
+    TalcVersion {
+        offender: String,
+        required: VersionReq,
+    },
+    Incompatible {
+        offender: String,
+        other: String,
+    },
+    /// every mod depends on the next, and the last on the first
+    Circular {
+        cycle: Vec<String>,
+    },
 }
 
 impl Display for ModLoadError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Failed to load mods.\n{}\n{}", self.offender.name, self.reason)
+        match self {
+            Self::InvalidInfo { path, reason } => {
+                write!(f, "Could not read {}: {reason}", path.display())
+            }
+            Self::Duplicate { name } => write!(f, "Mod {name} is installed twice."),
+            Self::MissingDependency {
+                offender,
+                dependency,
+            } => write!(
+                f,
+                "Mod {offender} requires {dependency}, which is not installed."
+            ),
+            Self::DependencyVersion {
+                offender,
+                dependency,
+                required,
+                installed,
+            } => write!(
+                f,
+                "Mod {offender} requires {dependency} {required}, but {dependency} {installed} is installed."
+            ),
+            Self::TalcVersion { offender, required } => write!(
+                f,
+                "Mod {offender} requires talc {required}, but this is talc {}.",
+                talc_version()
+            ),
+            Self::Incompatible { offender, other } => {
+                write!(f, "Mod {offender} is incompatible with {other}.")
+            }
+            Self::Circular { cycle } => {
+                write!(
+                    f,
+                    "Mods depend on each other in a circle: {}",
+                    cycle.join(" -> ")
+                )
+            }
+        }
     }
 }
 
 impl Error for ModLoadError {}
 
-impl From<RhaiError> for ModLoadError {
-    fn from(value: RhaiError) -> Self {
-        todo!()
+/// The version of the game, which mods check their `talc_version` against.
+fn talc_version() -> Version {
+    Version::parse(env!("CARGO_PKG_VERSION")).expect("The crate version is a valid version.")
+}
+
+/// Checks every mod's versions, and orders mods so that every mod loads after its dependencies.
+/// Mods that don't depend on each other load in alphabetical order, so the order never depends on the filesystem.
+fn sort_mods(mods: Vec<Mod>) -> Result<Vec<Mod>, ModLoadError> {
+    let mut indices = BTreeMap::new();
+    for (i, mod_) in mods.iter().enumerate() {
+        if indices.insert(mod_.name.as_str(), i).is_some() {
+            return Err(ModLoadError::Duplicate {
+                name: mod_.name.clone(),
+            });
+        }
     }
+
+    // the mods each mod waits for, and the mods waiting for it
+    let mut waiting_for = vec![0; mods.len()];
+    let mut dependants = vec![vec![]; mods.len()];
+    for (i, mod_) in mods.iter().enumerate() {
+        if !mod_.talc_version.matches(&talc_version()) {
+            return Err(ModLoadError::TalcVersion {
+                offender: mod_.name.clone(),
+                required: mod_.talc_version.clone(),
+            });
+        }
+        for dependency in &mod_.dependencies {
+            // an installed mod that doesn't match an incompatible requirement is fine to have around
+            let installed = indices
+                .get(dependency.name.as_str())
+                .copied()
+                .filter(|&index| {
+                    dependency.kind != DependencyKind::Incompatible
+                        || dependency.version.matches(&mods[index].version)
+                });
+            if let Some(index) = installed
+                && dependency.kind != DependencyKind::Incompatible
+                && !dependency.version.matches(&mods[index].version)
+            {
+                return Err(ModLoadError::DependencyVersion {
+                    offender: mod_.name.clone(),
+                    dependency: dependency.name.clone(),
+                    required: dependency.version.clone(),
+                    installed: mods[index].version.clone(),
+                });
+            }
+            match (dependency.kind, installed) {
+                (DependencyKind::Required, None) => {
+                    return Err(ModLoadError::MissingDependency {
+                        offender: mod_.name.clone(),
+                        dependency: dependency.name.clone(),
+                    });
+                }
+                (DependencyKind::Incompatible, Some(_)) => {
+                    return Err(ModLoadError::Incompatible {
+                        offender: mod_.name.clone(),
+                        other: dependency.name.clone(),
+                    });
+                }
+                (DependencyKind::Required | DependencyKind::Optional, Some(index)) => {
+                    waiting_for[i] += 1;
+                    dependants[index].push(i);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let mut ready: BTreeSet<(&str, usize)> = mods
+        .iter()
+        .enumerate()
+        .filter(|&(i, _)| waiting_for[i] == 0)
+        .map(|(i, mod_)| (mod_.name.as_str(), i))
+        .collect();
+    let mut order = Vec::with_capacity(mods.len());
+    while let Some((_, i)) = ready.pop_first() {
+        order.push(i);
+        for &dependant in &dependants[i] {
+            waiting_for[dependant] -= 1;
+            if waiting_for[dependant] == 0 {
+                ready.insert((mods[dependant].name.as_str(), dependant));
+            }
+        }
+    }
+
+    if order.len() < mods.len() {
+        return Err(ModLoadError::Circular {
+            cycle: find_cycle(&mods, &indices, &waiting_for),
+        });
+    }
+
+    let mut mods: Vec<Option<Mod>> = mods.into_iter().map(Some).collect();
+    Ok(order
+        .into_iter()
+        .map(|i| mods[i].take().expect("Each mod is sorted once."))
+        .collect())
+}
+
+/// Every mod that is still waiting depends on another mod that is still waiting,
+/// so following those dependencies from any of them has to run in a circle.
+fn find_cycle(mods: &[Mod], indices: &BTreeMap<&str, usize>, waiting_for: &[usize]) -> Vec<String> {
+    let waiting_dependency = |i: usize| {
+        mods[i]
+            .dependencies
+            .iter()
+            .filter(|dependency| dependency.kind != DependencyKind::Incompatible)
+            .filter_map(|dependency| indices.get(dependency.name.as_str()).copied())
+            .find(|&dependency| waiting_for[dependency] > 0)
+            .expect("A waiting mod has a waiting dependency.")
+    };
+
+    let mut path = vec![];
+    let mut current = (0..mods.len())
+        .find(|&i| waiting_for[i] > 0)
+        .expect("Some mod is waiting.");
+    while !path.contains(&current) {
+        path.push(current);
+        current = waiting_dependency(current);
+    }
+    let start = path.iter().position(|&i| i == current).unwrap_or_default();
+    path[start..]
+        .iter()
+        .chain([&current])
+        .map(|&i| mods[i].name.clone())
+        .collect()
 }
-*/
 
-fn detect_mods() -> Box<[Mod]> {
+fn detect_mods() -> Result<Box<[Mod]>> {
     let mut mods: Vec<Mod> = vec![];
     let mods_path: PathBuf = "assets/mods".into();
 
-    for entry in fs::read_dir(mods_path).expect("Could not find mods directory.") {
-        let entry = entry.expect("Could not find mods directory.");
-        let path = entry.path();
+    for entry in fs::read_dir(&mods_path)
+        .with_context(|| format!("Could not find mods directory {}.", mods_path.display()))?
+    {
+        let path = entry
+            .with_context(|| format!("Could not read mods directory {}.", mods_path.display()))?
+            .path();
 
         // Check if the entry is a directory
         if path.is_dir() {
             // Check for info.toml in this directory
             let info_toml = path.join("info.toml");
             if info_toml.is_file() {
-                mods.push(Mod::from_path(&path));
+                mods.push(Mod::from_path(&path)?);
             }
         }
     }
 
-    mods.into_boxed_slice()
+    Ok(sort_mods(mods)?.into_boxed_slice())
+}
+
+/// Exposes every loaded mod to Lua as `mods[name] = version`, so mods can check for each other.
+fn set_mods_table(lua: &Lua, mods: &[Mod]) -> Result<()> {
+    let table = lua.create_table()?;
+    for mod_ in mods {
+        table.set(mod_.name.as_str(), mod_.version.to_string())?;
+    }
+    lua.globals().set("mods", table)?;
+    Ok(())
 }
 
 fn data_stage(lua: &Lua, mods: &[Mod]) -> Result<()> {
     for mod_ in mods {
         let chunk = fs::read_to_string(mod_.path.join("data.lua"))?;
-        lua.load(chunk).exec()?;
+        require::run_in_mod(lua, &mod_.name, "data.lua", chunk)?;
     }
     Ok(())
 }
@@ -119,7 +401,7 @@ fn data_stage(lua: &Lua, mods: &[Mod]) -> Result<()> {
 fn data_updates_stage(lua: &Lua, mods: &[Mod]) -> Result<()> {
     for mod_ in mods {
         let chunk = fs::read_to_string(mod_.path.join("data_updates.lua"))?;
-        lua.load(chunk).exec()?;
+        require::run_in_mod(lua, &mod_.name, "data_updates.lua", chunk)?;
     }
     Ok(())
 }
@@ -127,27 +409,56 @@ fn data_updates_stage(lua: &Lua, mods: &[Mod]) -> Result<()> {
 fn data_final_fixes_stage(lua: &Lua, mods: &[Mod]) -> Result<()> {
     for mod_ in mods {
         let chunk = fs::read_to_string(mod_.path.join("data_final_fixes.lua"))?;
-        lua.load(chunk).exec()?;
+        require::run_in_mod(lua, &mod_.name, "data_final_fixes.lua", chunk)?;
     }
     Ok(())
 }
 
-fn lua_setup(mut commands: Commands) {
-    let mods = detect_mods();
+/// Everything the mods defined.
+pub struct LoadedPrototypes {
+    pub blocks: BlockPrototypes,
+    pub chunk_tickets: ChunkTicketPrototypes,
+    pub biomes: BiomePrototypes,
+    pub resources: ResourcePrototypes,
+    pub features: FeaturePrototypes,
+    pub flat_presets: FlatPresetPrototypes,
+}
+
+/// Runs every data stage of every mod and parses the resulting data table.
+/// Does not touch the block registry, so this can also be used outside of the app.
+///
+/// # Errors
+/// If a mod's `info.toml` is invalid, its dependencies can't be satisfied, or its scripts fail to run.
+///
+/// # Panics
+/// If a prototype can not be parsed.
+pub fn load_prototypes() -> Result<LoadedPrototypes> {
+    let mods = detect_mods()?;
 
     let lua = Lua::new();
     lua.enable_jit(true);
 
-    //engine.set_module_resolver(FileModuleResolver::new_with_path("assets/mods"));
+    require::install(
+        &lua,
+        mods.iter()
+            .map(|mod_| (mod_.name.as_str(), mod_.path.clone())),
+    )
+    .context("Failed to set up require")?;
 
-    data_stage(&lua, &mods).expect("Failed to load data stage");
-    data_updates_stage(&lua, &mods).expect("Failed to load data updates stage");
-    data_final_fixes_stage(&lua, &mods).expect("Failed to load data final fixes stage");
+    set_mods_table(&lua, &mods).context("Failed to create mods table")?;
+    data_stage(&lua, &mods).context("Failed to load data stage")?;
+    data_updates_stage(&lua, &mods).context("Failed to load data updates stage")?;
+    data_final_fixes_stage(&lua, &mods).context("Failed to load data final fixes stage")?;
 
     let globals = lua.globals();
-    let data = globals.get::<Table>("data").unwrap();
+    let data = globals.get::<Table>("data")?;
 
     let mut block_prototypes = BlockPrototypesBuilder::new();
+    let mut chunk_ticket_prototypes = ChunkTicketPrototypesBuilder::new();
+    let mut biome_prototypes = BiomePrototypesBuilder::new();
+    let mut resource_prototypes = ResourcePrototypesBuilder::new();
+    let mut feature_prototypes = FeaturePrototypesBuilder::new();
+    let mut flat_preset_prototypes = FlatPresetPrototypesBuilder::new();
 
     data.for_each(|k: String, v: Value| {
         if k == "block" {
@@ -157,12 +468,170 @@ fn lua_setup(mut commands: Commands) {
                 );
                 Ok(())
             })?;
+        } else if k == "chunk-ticket" {
+            v.as_table().unwrap().for_each(|_: String, v: Value| {
+                chunk_ticket_prototypes.add(
+                    RawChunkTicketPrototype::from_lua(v, &lua)
+                        .expect("Could not parse chunk ticket prototype"),
+                );
+                Ok(())
+            })?;
+        } else if k == "biome" {
+            v.as_table().unwrap().for_each(|_: String, v: Value| {
+                biome_prototypes.add(
+                    RawBiomePrototype::from_lua(v, &lua).expect("Could not parse biome prototype"),
+                );
+                Ok(())
+            })?;
+        } else if k == "resource" {
+            v.as_table().unwrap().for_each(|_: String, v: Value| {
+                resource_prototypes.add(
+                    RawResourcePrototype::from_lua(v, &lua)
+                        .expect("Could not parse resource prototype"),
+                );
+                Ok(())
+            })?;
+        } else if k == "feature" {
+            v.as_table().unwrap().for_each(|_: String, v: Value| {
+                feature_prototypes.add(
+                    RawFeaturePrototype::from_lua(v, &lua)
+                        .expect("Could not parse feature prototype"),
+                );
+                Ok(())
+            })?;
+        } else if k == "flat-preset" {
+            v.as_table().unwrap().for_each(|_: String, v: Value| {
+                flat_preset_prototypes.add(
+                    RawFlatPresetPrototype::from_lua(v, &lua)
+                        .expect("Could not parse flat preset prototype"),
+                );
+                Ok(())
+            })?;
         }
         Ok(())
     })
-    .expect("Found non-string key in data table.");
+    .context("Found non-string key in data table.")?;
+
+    Ok(LoadedPrototypes {
+        blocks: block_prototypes.build(),
+        chunk_tickets: chunk_ticket_prototypes.build(),
+        biomes: biome_prototypes.build(),
+        resources: resource_prototypes.build(),
+        features: feature_prototypes.build(),
+        flat_presets: flat_preset_prototypes.build(),
+    })
+}
+
+fn lua_setup(mut commands: Commands, mut exit: EventWriter<AppExit>) {
+    let prototypes = match load_prototypes() {
+        Ok(prototypes) => prototypes,
+        Err(error) => {
+            error!("Failed to load mods: {error:#}");
+            exit.write(AppExit::error());
+            return;
+        }
+    };
+
+    set_block_registry(&prototypes.blocks);
+    commands.insert_resource(prototypes.blocks);
+    commands.insert_resource(prototypes.chunk_tickets);
+    commands.insert_resource(prototypes.biomes);
+    commands.insert_resource(prototypes.resources);
+    commands.insert_resource(prototypes.features);
+    commands.insert_resource(prototypes.flat_presets);
+}
+
+#[test]
+fn mods_load_after_their_dependencies() {
+    let mod_ = |name: &str, dependencies: &[&str]| Mod {
+        name: name.to_string(),
+        path: PathBuf::new(),
+        version: Version::new(0, 1, 0),
+        talc_version: VersionReq::STAR,
+        dependencies: dependencies
+            .iter()
+            .map(|key| Dependency::new(key, VersionReq::STAR))
+            .collect(),
+    };
+    let names = |mods: Vec<Mod>| mods.into_iter().map(|mod_| mod_.name).collect::<Vec<_>>();
+
+    let sorted = sort_mods(vec![
+        mod_("base", &["core"]),
+        mod_("zinc", &[]),
+        mod_("addon", &["base", "?missing", "!unknown"]),
+        mod_("core", &[]),
+    ]);
+    assert_eq!(names(sorted.unwrap()), ["core", "base", "addon", "zinc"]);
+
+    let missing = sort_mods(vec![mod_("base", &["core"])]);
+    assert_eq!(
+        missing.unwrap_err(),
+        ModLoadError::MissingDependency {
+            offender: "base".to_string(),
+            dependency: "core".to_string()
+        }
+    );
+
+    let incompatible = sort_mods(vec![mod_("base", &["!core"]), mod_("core", &[])]);
+    assert!(matches!(
+        incompatible.unwrap_err(),
+        ModLoadError::Incompatible { .. }
+    ));
+
+    let circular = sort_mods(vec![
+        mod_("a", &["b"]),
+        mod_("b", &["c"]),
+        mod_("c", &["a"]),
+        mod_("core", &[]),
+    ]);
+    assert_eq!(
+        circular.unwrap_err(),
+        ModLoadError::Circular {
+            cycle: vec!["a", "b", "c", "a"]
+                .into_iter()
+                .map(String::from)
+                .collect()
+        }
+    );
+}
+
+#[test]
+fn mod_versions_are_checked() {
+    let mod_ = |name: &str, version: &str, talc_version: &str, dependencies: &[(&str, &str)]| Mod {
+        name: name.to_string(),
+        path: PathBuf::new(),
+        version: Version::parse(version).unwrap(),
+        talc_version: VersionReq::parse(talc_version).unwrap(),
+        dependencies: dependencies
+            .iter()
+            .map(|(key, requirement)| Dependency::new(key, VersionReq::parse(requirement).unwrap()))
+            .collect(),
+    };
 
-    let block_prototypes = block_prototypes.build();
-    set_block_registry(&block_prototypes);
-    commands.insert_resource(block_prototypes);
+    assert!(
+        sort_mods(vec![
+            mod_("core", "0.3.1", "*", &[]),
+            mod_("base", "0.1.0", "*", &[("core", ">= 0.3")]),
+        ])
+        .is_ok()
+    );
+    assert!(matches!(
+        sort_mods(vec![
+            mod_("core", "0.1.0", "*", &[]),
+            mod_("base", "0.1.0", "*", &[("core", ">= 0.3")]),
+        ]),
+        Err(ModLoadError::DependencyVersion { .. })
+    ));
+    // only the matching versions are incompatible
+    assert!(
+        sort_mods(vec![
+            mod_("core", "0.1.0", "*", &[]),
+            mod_("base", "0.1.0", "*", &[("!core", ">= 0.3")]),
+        ])
+        .is_ok()
+    );
+    assert!(matches!(
+        sort_mods(vec![mod_("core", "0.1.0", "999", &[])]),
+        Err(ModLoadError::TalcVersion { .. })
+    ));
 }
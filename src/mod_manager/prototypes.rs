@@ -9,7 +9,11 @@ use bevy::color::Color;
 use bevy::prelude::*;
 use mlua::FromLua;
 
-use super::lua_conversions::LuaColor;
+use crate::chunky::chunk::CHUNK_SIZE_I32;
+use crate::chunky::chunk_ticket::TicketLevel;
+use crate::position::ChunkPosition;
+
+use super::lua_conversions::{LuaColor, LuaIVec3};
 
 /// Prototypes are assembled from lua with a pipeline system.
 /// This struct repersents stage 1:
@@ -25,7 +29,7 @@ where
 {
 }
 
-pub(super) trait PrototypesBuilder {
+pub(crate) trait PrototypesBuilder {
     type BuiltFrom: RawPrototype;
     type Final: Prototypes;
     fn new() -> Self;
@@ -54,7 +58,7 @@ impl Prototypes for BlockPrototypes {
     }
 }
 
-pub(super) struct BlockPrototypesBuilder(usize, BTreeMap<&'static str, &'static BlockPrototype>);
+pub(crate) struct BlockPrototypesBuilder(usize, BTreeMap<&'static str, &'static BlockPrototype>);
 
 impl PrototypesBuilder for BlockPrototypesBuilder {
     type BuiltFrom = RawBlockPrototype;
@@ -89,11 +93,11 @@ impl PrototypesBuilder for BlockPrototypesBuilder {
 }
 
 #[derive(Clone)]
-pub(super) struct RawBlockPrototype {
-    name: Box<str>,
-    is_transparent: bool,
-    is_meshable: bool,
-    color: Color,
+pub(crate) struct RawBlockPrototype {
+    pub(crate) name: Box<str>,
+    pub(crate) is_transparent: bool,
+    pub(crate) is_meshable: bool,
+    pub(crate) color: Color,
 }
 
 impl RawPrototype for RawBlockPrototype {}
@@ -152,3 +156,752 @@ impl PartialEq for BlockPrototype {
 }
 
 impl Prototype for BlockPrototype {}
+
+#[derive(Resource, Clone)]
+pub struct ChunkTicketPrototypes(BTreeMap<&'static str, &'static ChunkTicketPrototype>);
+
+impl Prototypes for ChunkTicketPrototypes {
+    type T = ChunkTicketPrototype;
+
+    fn get(&self, name: &str) -> Option<&'static ChunkTicketPrototype> {
+        self.0.get(name).map(|v| &**v)
+    }
+
+    fn iter(&self) -> Iter<'_, &'static str, &'static Self::T> {
+        self.0.iter()
+    }
+}
+
+pub(super) struct ChunkTicketPrototypesBuilder(
+    BTreeMap<&'static str, &'static ChunkTicketPrototype>,
+);
+
+impl PrototypesBuilder for ChunkTicketPrototypesBuilder {
+    type BuiltFrom = RawChunkTicketPrototype;
+    type Final = ChunkTicketPrototypes;
+
+    fn new() -> Self {
+        Self(BTreeMap::default())
+    }
+
+    fn add(&mut self, prototype: Self::BuiltFrom) {
+        let prototype = ChunkTicketPrototype {
+            name: prototype.name,
+            position: prototype.position,
+            radius: prototype.radius,
+            level: prototype.level,
+        };
+
+        let name = prototype.name.clone();
+        assert!(
+            self.0
+                .insert(Box::leak(name.clone()), Box::leak(prototype.into()))
+                .is_none(),
+            "Prototype {name} registered twice."
+        );
+    }
+
+    fn build(self) -> Self::Final {
+        ChunkTicketPrototypes(self.0)
+    }
+}
+
+#[derive(Clone)]
+pub(super) struct RawChunkTicketPrototype {
+    name: Box<str>,
+    position: ChunkPosition,
+    radius: u32,
+    level: TicketLevel,
+}
+
+impl RawPrototype for RawChunkTicketPrototype {}
+
+impl FromLua for RawChunkTicketPrototype {
+    fn from_lua(value: mlua::Value, _lua: &mlua::Lua) -> mlua::Result<Self> {
+        let error = |message: String| mlua::Error::ToLuaConversionError {
+            message: Some(message),
+            to: "Rust Chunk Ticket Prototype",
+            from: "Lua Chunk Ticket Prototype".to_string(),
+        };
+
+        let Some(table) = value.as_table() else {
+            Err(error(
+                "Chunk ticket prototypes are expected to be a table.".to_string(),
+            ))?
+        };
+
+        let name: Box<str> = table
+            .get::<String>("name")
+            .context("Could not parse ChunkTicketPrototype::name field.")?
+            .into();
+        let position = ChunkPosition(
+            table
+                .get::<LuaIVec3>("position")
+                .context("Could not parse ChunkTicketPrototype::position field.")?
+                .0,
+        );
+        let radius = table
+            .get::<Option<u32>>("radius")
+            .context("Could not parse ChunkTicketPrototype::radius field.")?
+            .unwrap_or(0);
+        let level = match table
+            .get::<Option<String>>("level")
+            .context("Could not parse ChunkTicketPrototype::level field.")?
+            .as_deref()
+        {
+            None | Some("data") => TicketLevel::Data,
+            Some("simulation") => TicketLevel::Simulation,
+            Some(level) => Err(error(format!(
+                "Unknown chunk ticket level `{level}`. Expected `data` or `simulation`."
+            )))?,
+        };
+
+        Ok(Self {
+            name,
+            position,
+            radius,
+            level,
+        })
+    }
+}
+
+/// Keeps an area of chunks loaded from the moment the prototypes are loaded.
+#[derive(Debug)]
+pub struct ChunkTicketPrototype {
+    pub name: Box<str>,
+    /// the center of the area, in chunks
+    pub position: ChunkPosition,
+    pub radius: u32,
+    pub level: TicketLevel,
+}
+
+impl PartialEq for ChunkTicketPrototype {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::addr_eq(self, other)
+    }
+}
+
+impl Prototype for ChunkTicketPrototype {}
+
+#[derive(Resource, Clone)]
+pub struct BiomePrototypes(BTreeMap<&'static str, &'static BiomePrototype>);
+
+impl Prototypes for BiomePrototypes {
+    type T = BiomePrototype;
+
+    fn get(&self, name: &str) -> Option<&'static BiomePrototype> {
+        self.0.get(name).map(|v| &**v)
+    }
+
+    fn iter(&self) -> Iter<'_, &'static str, &'static Self::T> {
+        self.0.iter()
+    }
+}
+
+pub(crate) struct BiomePrototypesBuilder(BTreeMap<&'static str, &'static BiomePrototype>);
+
+impl PrototypesBuilder for BiomePrototypesBuilder {
+    type BuiltFrom = RawBiomePrototype;
+    type Final = BiomePrototypes;
+
+    fn new() -> Self {
+        Self(BTreeMap::default())
+    }
+
+    fn add(&mut self, prototype: Self::BuiltFrom) {
+        let prototype = BiomePrototype {
+            name: prototype.name,
+            temperature: prototype.temperature,
+            humidity: prototype.humidity,
+            surface_block: prototype.surface_block,
+            subsurface_block: prototype.subsurface_block,
+            filler_block: prototype.filler_block,
+            subsurface_depth: prototype.subsurface_depth,
+            height: prototype.height,
+            height_variation: prototype.height_variation,
+            overhang: prototype.overhang,
+        };
+
+        let name = prototype.name.clone();
+        assert!(
+            self.0
+                .insert(Box::leak(name.clone()), Box::leak(prototype.into()))
+                .is_none(),
+            "Prototype {name} registered twice."
+        );
+    }
+
+    fn build(self) -> Self::Final {
+        BiomePrototypes(self.0)
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct RawBiomePrototype {
+    pub(crate) name: Box<str>,
+    pub(crate) temperature: f32,
+    pub(crate) humidity: f32,
+    pub(crate) surface_block: Box<str>,
+    pub(crate) subsurface_block: Box<str>,
+    pub(crate) filler_block: Box<str>,
+    pub(crate) subsurface_depth: u32,
+    pub(crate) height: f32,
+    pub(crate) height_variation: f32,
+    pub(crate) overhang: f32,
+}
+
+impl RawPrototype for RawBiomePrototype {}
+
+impl FromLua for RawBiomePrototype {
+    fn from_lua(value: mlua::Value, _lua: &mlua::Lua) -> mlua::Result<Self> {
+        let error = |message: String| mlua::Error::ToLuaConversionError {
+            message: Some(message),
+            to: "Rust Biome Prototype",
+            from: "Lua Biome Prototype".to_string(),
+        };
+
+        let Some(table) = value.as_table() else {
+            Err(error(
+                "Biome prototypes are expected to be a table.".to_string(),
+            ))?
+        };
+
+        let name: Box<str> = table
+            .get::<String>("name")
+            .context("Could not parse BiomePrototype::name field.")?
+            .into();
+        let climate = |field: &str| -> mlua::Result<f32> {
+            let value = table
+                .get::<f32>(field)
+                .with_context(|| format!("Could not parse BiomePrototype::{field} field."))?;
+            if !(-1.0..=1.0).contains(&value) {
+                return Err(error(format!(
+                    "Biome {name} has {field} {value}. Expected a value between -1 and 1."
+                )));
+            }
+            Ok(value)
+        };
+        let temperature = climate("temperature")?;
+        let humidity = climate("humidity")?;
+        let surface_block: Box<str> = table
+            .get::<String>("surface_block")
+            .context("Could not parse BiomePrototype::surface_block field.")?
+            .into();
+        let subsurface_block: Box<str> = table
+            .get::<Option<String>>("subsurface_block")
+            .context("Could not parse BiomePrototype::subsurface_block field.")?
+            .map_or_else(|| surface_block.clone(), Into::into);
+        let filler_block: Box<str> = table
+            .get::<Option<String>>("filler_block")
+            .context("Could not parse BiomePrototype::filler_block field.")?
+            .map_or_else(|| subsurface_block.clone(), Into::into);
+        let subsurface_depth = table
+            .get::<Option<u32>>("subsurface_depth")
+            .context("Could not parse BiomePrototype::subsurface_depth field.")?
+            .unwrap_or(3);
+        let height = table
+            .get::<Option<f32>>("height")
+            .context("Could not parse BiomePrototype::height field.")?
+            .unwrap_or(200.);
+        let height_variation = table
+            .get::<Option<f32>>("height_variation")
+            .context("Could not parse BiomePrototype::height_variation field.")?
+            .unwrap_or(30.);
+        let overhang = table
+            .get::<Option<f32>>("overhang")
+            .context("Could not parse BiomePrototype::overhang field.")?
+            .unwrap_or(0.);
+
+        Ok(Self {
+            name,
+            temperature,
+            humidity,
+            surface_block,
+            subsurface_block,
+            filler_block,
+            subsurface_depth,
+            height,
+            height_variation,
+            overhang,
+        })
+    }
+}
+
+/// A region of terrain with its own blocks and shape.
+/// Each biome claims a point in climate space, and the world picks the biome whose point is
+/// closest to the climate noise at each column.
+#[derive(Debug)]
+pub struct BiomePrototype {
+    pub name: Box<str>,
+    /// between -1 (cold) and 1 (hot)
+    pub temperature: f32,
+    /// between -1 (dry) and 1 (wet)
+    pub humidity: f32,
+    /// the topmost block of the terrain
+    pub surface_block: Box<str>,
+    /// the blocks below the surface, `subsurface_depth` deep
+    pub subsurface_block: Box<str>,
+    /// everything below the subsurface
+    pub filler_block: Box<str>,
+    pub subsurface_depth: u32,
+    /// the height the terrain is centered on, in blocks
+    pub height: f32,
+    /// how far hills rise above and valleys sink below `height`
+    pub height_variation: f32,
+    /// how many blocks 3D noise pushes the surface up or down. 0 gives plain hills, larger values give cliffs and overhangs.
+    pub overhang: f32,
+}
+
+impl PartialEq for BiomePrototype {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::addr_eq(self, other)
+    }
+}
+
+impl Prototype for BiomePrototype {}
+
+#[derive(Resource, Clone)]
+pub struct ResourcePrototypes(BTreeMap<&'static str, &'static ResourcePrototype>);
+
+impl Prototypes for ResourcePrototypes {
+    type T = ResourcePrototype;
+
+    fn get(&self, name: &str) -> Option<&'static ResourcePrototype> {
+        self.0.get(name).map(|v| &**v)
+    }
+
+    fn iter(&self) -> Iter<'_, &'static str, &'static Self::T> {
+        self.0.iter()
+    }
+}
+
+pub(crate) struct ResourcePrototypesBuilder(BTreeMap<&'static str, &'static ResourcePrototype>);
+
+impl PrototypesBuilder for ResourcePrototypesBuilder {
+    type BuiltFrom = RawResourcePrototype;
+    type Final = ResourcePrototypes;
+
+    fn new() -> Self {
+        Self(BTreeMap::default())
+    }
+
+    fn add(&mut self, prototype: Self::BuiltFrom) {
+        let prototype = ResourcePrototype {
+            name: prototype.name,
+            block: prototype.block,
+            autoplace: prototype.autoplace,
+        };
+
+        let name = prototype.name.clone();
+        assert!(
+            self.0
+                .insert(Box::leak(name.clone()), Box::leak(prototype.into()))
+                .is_none(),
+            "Prototype {name} registered twice."
+        );
+    }
+
+    fn build(self) -> Self::Final {
+        ResourcePrototypes(self.0)
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct RawResourcePrototype {
+    pub(crate) name: Box<str>,
+    pub(crate) block: Box<str>,
+    pub(crate) autoplace: AutoplaceSettings,
+}
+
+impl RawPrototype for RawResourcePrototype {}
+
+impl FromLua for RawResourcePrototype {
+    fn from_lua(value: mlua::Value, _lua: &mlua::Lua) -> mlua::Result<Self> {
+        let error = |message: String| mlua::Error::ToLuaConversionError {
+            message: Some(message),
+            to: "Rust Resource Prototype",
+            from: "Lua Resource Prototype".to_string(),
+        };
+
+        let Some(table) = value.as_table() else {
+            Err(error(
+                "Resource prototypes are expected to be a table.".to_string(),
+            ))?
+        };
+
+        let name: Box<str> = table
+            .get::<String>("name")
+            .context("Could not parse ResourcePrototype::name field.")?
+            .into();
+        let block: Box<str> = table
+            .get::<String>("block")
+            .context("Could not parse ResourcePrototype::block field.")?
+            .into();
+        let Some(autoplace) = table
+            .get::<Option<mlua::Table>>("autoplace")
+            .context("Could not parse ResourcePrototype::autoplace field.")?
+        else {
+            return Ok(Self {
+                name,
+                block,
+                autoplace: AutoplaceSettings::default(),
+            });
+        };
+
+        let defaults = AutoplaceSettings::default();
+        let multiplier = |field: &str, default: f32| -> mlua::Result<f32> {
+            let value = autoplace
+                .get::<Option<f32>>(field)
+                .with_context(|| format!("Could not parse AutoplaceSettings::{field} field."))?
+                .unwrap_or(default);
+            if value < 0. {
+                return Err(error(format!(
+                    "Resource {name} has autoplace {field} {value}. Expected a value of at least 0."
+                )));
+            }
+            Ok(value)
+        };
+        let frequency = multiplier("frequency", defaults.frequency)?;
+        let size = multiplier("size", defaults.size)?;
+        let richness = multiplier("richness", defaults.richness)?;
+        let distance_scaling = multiplier("distance_scaling", defaults.distance_scaling)?;
+        let min_height = autoplace
+            .get::<Option<i32>>("min_height")
+            .context("Could not parse AutoplaceSettings::min_height field.")?
+            .unwrap_or(defaults.min_height);
+        let max_height = autoplace
+            .get::<Option<i32>>("max_height")
+            .context("Could not parse AutoplaceSettings::max_height field.")?
+            .unwrap_or(defaults.max_height);
+        if min_height > max_height {
+            return Err(error(format!(
+                "Resource {name} has autoplace min_height {min_height} above max_height {max_height}."
+            )));
+        }
+
+        Ok(Self {
+            name,
+            block,
+            autoplace: AutoplaceSettings {
+                frequency,
+                size,
+                richness,
+                min_height,
+                max_height,
+                distance_scaling,
+            },
+        })
+    }
+}
+
+/// Where a resource's patches are placed. `frequency`, `size` and `richness` are multipliers, 1 is normal.
+#[derive(Clone, Copy, Debug)]
+pub struct AutoplaceSettings {
+    /// how many patches there are
+    pub frequency: f32,
+    /// how large each patch is
+    pub size: f32,
+    /// how much of a patch is ore
+    pub richness: f32,
+    /// patch centers are placed between these heights, in blocks
+    pub min_height: i32,
+    pub max_height: i32,
+    /// how much larger and richer patches get per 1000 blocks from spawn. 0 keeps them the same everywhere.
+    pub distance_scaling: f32,
+}
+
+impl Default for AutoplaceSettings {
+    fn default() -> Self {
+        Self {
+            frequency: 1.,
+            size: 1.,
+            richness: 1.,
+            min_height: -160,
+            max_height: 200,
+            distance_scaling: 1.,
+        }
+    }
+}
+
+/// Something to mine. Worldgen places patches of its block underground.
+#[derive(Debug)]
+pub struct ResourcePrototype {
+    pub name: Box<str>,
+    /// the block the patches are made of
+    pub block: Box<str>,
+    pub autoplace: AutoplaceSettings,
+}
+
+impl PartialEq for ResourcePrototype {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::addr_eq(self, other)
+    }
+}
+
+impl Prototype for ResourcePrototype {}
+
+#[derive(Resource, Clone)]
+pub struct FeaturePrototypes(BTreeMap<&'static str, &'static FeaturePrototype>);
+
+impl Prototypes for FeaturePrototypes {
+    type T = FeaturePrototype;
+
+    fn get(&self, name: &str) -> Option<&'static FeaturePrototype> {
+        self.0.get(name).map(|v| &**v)
+    }
+
+    fn iter(&self) -> Iter<'_, &'static str, &'static Self::T> {
+        self.0.iter()
+    }
+}
+
+pub(crate) struct FeaturePrototypesBuilder(BTreeMap<&'static str, &'static FeaturePrototype>);
+
+impl PrototypesBuilder for FeaturePrototypesBuilder {
+    type BuiltFrom = RawFeaturePrototype;
+    type Final = FeaturePrototypes;
+
+    fn new() -> Self {
+        Self(BTreeMap::default())
+    }
+
+    fn add(&mut self, prototype: Self::BuiltFrom) {
+        let prototype = FeaturePrototype {
+            name: prototype.name,
+            frequency: prototype.frequency,
+            biomes: prototype.biomes,
+            place_on: prototype.place_on,
+            blocks: prototype.blocks,
+        };
+
+        let name = prototype.name.clone();
+        assert!(
+            self.0
+                .insert(Box::leak(name.clone()), Box::leak(prototype.into()))
+                .is_none(),
+            "Prototype {name} registered twice."
+        );
+    }
+
+    fn build(self) -> Self::Final {
+        FeaturePrototypes(self.0)
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct RawFeaturePrototype {
+    pub(crate) name: Box<str>,
+    pub(crate) frequency: f32,
+    pub(crate) biomes: Vec<Box<str>>,
+    pub(crate) place_on: Vec<Box<str>>,
+    pub(crate) blocks: Vec<(IVec3, Box<str>)>,
+}
+
+impl RawPrototype for RawFeaturePrototype {}
+
+impl FromLua for RawFeaturePrototype {
+    fn from_lua(value: mlua::Value, _lua: &mlua::Lua) -> mlua::Result<Self> {
+        let error = |message: String| mlua::Error::ToLuaConversionError {
+            message: Some(message),
+            to: "Rust Feature Prototype",
+            from: "Lua Feature Prototype".to_string(),
+        };
+
+        let Some(table) = value.as_table() else {
+            Err(error(
+                "Feature prototypes are expected to be a table.".to_string(),
+            ))?
+        };
+
+        let name: Box<str> = table
+            .get::<String>("name")
+            .context("Could not parse FeaturePrototype::name field.")?
+            .into();
+        let frequency = table
+            .get::<f32>("frequency")
+            .context("Could not parse FeaturePrototype::frequency field.")?;
+        let names = |field: &str| -> mlua::Result<Vec<Box<str>>> {
+            Ok(table
+                .get::<Option<Vec<String>>>(field)
+                .with_context(|| format!("Could not parse FeaturePrototype::{field} field."))?
+                .unwrap_or_default()
+                .into_iter()
+                .map(Into::into)
+                .collect())
+        };
+        let biomes = names("biomes")?;
+        let place_on = names("place_on")?;
+
+        let mut blocks = vec![];
+        for entry in table
+            .get::<mlua::Table>("blocks")
+            .context("Could not parse FeaturePrototype::blocks field.")?
+            .sequence_values::<mlua::Table>()
+        {
+            let entry = entry.context("Could not parse FeaturePrototype::blocks field.")?;
+            let position = entry
+                .get::<LuaIVec3>("position")
+                .context("Could not parse FeaturePrototype::blocks position.")?
+                .0;
+            // features may only reach into the chunks next to the one they are placed from
+            if position.abs().max_element() > CHUNK_SIZE_I32 {
+                return Err(error(format!(
+                    "Feature {name} has a block at {position}, more than {CHUNK_SIZE_I32} blocks from its origin."
+                )));
+            }
+            let block: Box<str> = entry
+                .get::<String>("block")
+                .context("Could not parse FeaturePrototype::blocks block.")?
+                .into();
+            blocks.push((position, block));
+        }
+
+        Ok(Self {
+            name,
+            frequency,
+            biomes,
+            place_on,
+            blocks,
+        })
+    }
+}
+
+/// Something placed on top of the terrain, such as a tree or a boulder. May cross chunk borders.
+#[derive(Debug)]
+pub struct FeaturePrototype {
+    pub name: Box<str>,
+    /// how many times per chunk the feature tries to place itself
+    pub frequency: f32,
+    /// the biomes the feature appears in. Empty means every biome.
+    pub biomes: Vec<Box<str>>,
+    /// the blocks the feature may stand on. Empty means any solid block.
+    pub place_on: Vec<Box<str>>,
+    /// the blocks of the feature, relative to the air block above the surface it stands on.
+    /// They only replace air.
+    pub blocks: Vec<(IVec3, Box<str>)>,
+}
+
+impl PartialEq for FeaturePrototype {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::addr_eq(self, other)
+    }
+}
+
+impl Prototype for FeaturePrototype {}
+
+#[derive(Resource, Clone)]
+pub struct FlatPresetPrototypes(BTreeMap<&'static str, &'static FlatPresetPrototype>);
+
+impl Prototypes for FlatPresetPrototypes {
+    type T = FlatPresetPrototype;
+
+    fn get(&self, name: &str) -> Option<&'static FlatPresetPrototype> {
+        self.0.get(name).map(|v| &**v)
+    }
+
+    fn iter(&self) -> Iter<'_, &'static str, &'static Self::T> {
+        self.0.iter()
+    }
+}
+
+pub(crate) struct FlatPresetPrototypesBuilder(BTreeMap<&'static str, &'static FlatPresetPrototype>);
+
+impl PrototypesBuilder for FlatPresetPrototypesBuilder {
+    type BuiltFrom = RawFlatPresetPrototype;
+    type Final = FlatPresetPrototypes;
+
+    fn new() -> Self {
+        Self(BTreeMap::default())
+    }
+
+    fn add(&mut self, prototype: Self::BuiltFrom) {
+        let prototype = FlatPresetPrototype {
+            name: prototype.name,
+            layers: prototype.layers,
+        };
+
+        let name = prototype.name.clone();
+        assert!(
+            self.0
+                .insert(Box::leak(name.clone()), Box::leak(prototype.into()))
+                .is_none(),
+            "Prototype {name} registered twice."
+        );
+    }
+
+    fn build(self) -> Self::Final {
+        FlatPresetPrototypes(self.0)
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct RawFlatPresetPrototype {
+    pub(crate) name: Box<str>,
+    pub(crate) layers: Vec<FlatLayer>,
+}
+
+impl RawPrototype for RawFlatPresetPrototype {}
+
+impl FromLua for RawFlatPresetPrototype {
+    fn from_lua(value: mlua::Value, _lua: &mlua::Lua) -> mlua::Result<Self> {
+        let error = |message: String| mlua::Error::ToLuaConversionError {
+            message: Some(message),
+            to: "Rust Flat Preset Prototype",
+            from: "Lua Flat Preset Prototype".to_string(),
+        };
+
+        let Some(table) = value.as_table() else {
+            Err(error(
+                "Flat preset prototypes are expected to be a table.".to_string(),
+            ))?
+        };
+
+        let name: Box<str> = table
+            .get::<String>("name")
+            .context("Could not parse FlatPresetPrototype::name field.")?
+            .into();
+
+        let mut layers = vec![];
+        for layer in table
+            .get::<mlua::Table>("layers")
+            .context("Could not parse FlatPresetPrototype::layers field.")?
+            .sequence_values::<mlua::Table>()
+        {
+            let layer = layer.context("Could not parse FlatPresetPrototype::layers field.")?;
+            let block: Box<str> = layer
+                .get::<String>("block")
+                .context("Could not parse FlatPresetPrototype::layers block.")?
+                .into();
+            let thickness = layer
+                .get::<u32>("thickness")
+                .context("Could not parse FlatPresetPrototype::layers thickness.")?;
+            layers.push(FlatLayer { block, thickness });
+        }
+
+        Ok(Self { name, layers })
+    }
+}
+
+/// A layer of a flat world.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct FlatLayer {
+    pub block: Box<str>,
+    /// in blocks
+    pub thickness: u32,
+}
+
+/// The layers of a flat world, for picking by name when a flat world is created.
+#[derive(Debug)]
+pub struct FlatPresetPrototype {
+    pub name: Box<str>,
+    /// from the bottom up
+    pub layers: Vec<FlatLayer>,
+}
+
+impl PartialEq for FlatPresetPrototype {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::addr_eq(self, other)
+    }
+}
+
+impl Prototype for FlatPresetPrototype {}
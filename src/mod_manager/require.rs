@@ -0,0 +1,180 @@
+//! `require` for mod scripts.
+//!
+//! `require("__core__/lib/serpent")` loads `lib/serpent.lua` from the `core` mod, and `require("lib/serpent")`
+//! (or `require("lib.serpent")`) loads it from the mod whose script is running. Paths can't leave the mod's directory.
+//! Every module runs once per Lua state, later calls return what it returned the first time.
+
+use std::{collections::BTreeMap, fs, path::PathBuf};
+
+use mlua::{Lua, Table, Value};
+
+/// Where the modules that already ran are kept, in the Lua registry.
+const LOADED_MODULES: &str = "talc_loaded_modules";
+
+/// Stored in the Lua state's app data.
+struct ModuleState {
+    /// the directory of every loaded mod
+    mods: BTreeMap<String, PathBuf>,
+    /// the mods whose scripts are running, innermost last
+    running: Vec<String>,
+    /// the modules that are running, to catch modules that require each other
+    loading: Vec<String>,
+}
+
+/// Replaces Lua's `require` with one that only loads modules from `mods`.
+pub(super) fn install<'a>(
+    lua: &Lua,
+    mods: impl IntoIterator<Item = (&'a str, PathBuf)>,
+) -> mlua::Result<()> {
+    lua.set_app_data(ModuleState {
+        mods: mods
+            .into_iter()
+            .map(|(name, path)| (name.to_string(), path))
+            .collect(),
+        running: vec![],
+        loading: vec![],
+    });
+    lua.set_named_registry_value(LOADED_MODULES, lua.create_table()?)?;
+    lua.globals().set(
+        "require",
+        lua.create_function(|lua, name: String| require(lua, &name))?,
+    )
+}
+
+/// Runs one of a mod's scripts, so that `require` inside it resolves relative to that mod.
+pub(super) fn run_in_mod(
+    lua: &Lua,
+    mod_name: &str,
+    file: &str,
+    source: String,
+) -> mlua::Result<()> {
+    let chunk = lua.load(source).set_name(format!("@__{mod_name}__/{file}"));
+    in_mod(lua, mod_name, || chunk.exec())
+}
+
+fn in_mod<T>(lua: &Lua, mod_name: &str, run: impl FnOnce() -> mlua::Result<T>) -> mlua::Result<T> {
+    state(lua)?.running.push(mod_name.to_string());
+    let result = run();
+    state(lua)?.running.pop();
+    result
+}
+
+fn state(lua: &Lua) -> mlua::Result<mlua::AppDataRefMut<'_, ModuleState>> {
+    lua.app_data_mut::<ModuleState>()
+        .ok_or_else(|| mlua::Error::runtime("require is not installed in this Lua state."))
+}
+
+fn require(lua: &Lua, name: &str) -> mlua::Result<Value> {
+    let (mod_name, file, path) = resolve(lua, name)?;
+    let key = format!("__{mod_name}__/{file}");
+
+    let loaded: Table = lua.named_registry_value(LOADED_MODULES)?;
+    let cached: Value = loaded.get(key.as_str())?;
+    if !cached.is_nil() {
+        return Ok(cached);
+    }
+    if state(lua)?.loading.contains(&key) {
+        return Err(mlua::Error::runtime(format!(
+            "Module {key} requires itself while it is loading."
+        )));
+    }
+
+    let source = fs::read_to_string(&path)
+        .map_err(|error| mlua::Error::runtime(format!("Could not read module {key}: {error}")))?;
+    state(lua)?.loading.push(key.clone());
+    let result = in_mod(lua, &mod_name, || {
+        lua.load(source).set_name(format!("@{key}")).eval::<Value>()
+    });
+    state(lua)?.loading.pop();
+
+    // like Lua's require, a module that returns nothing is remembered as `true`
+    let value = match result? {
+        Value::Nil => Value::Boolean(true),
+        value => value,
+    };
+    loaded.set(key.as_str(), value.clone())?;
+    Ok(value)
+}
+
+/// Finds the mod and the file a module name refers to.
+/// Returns the mod's name, the file relative to the mod, and the file's full path.
+fn resolve(lua: &Lua, name: &str) -> mlua::Result<(String, String, PathBuf)> {
+    let error = |reason: &str| mlua::Error::runtime(format!("Can't require `{name}`: {reason}"));
+    let state = state(lua)?;
+
+    let (mod_name, module) = match name.strip_prefix("__") {
+        Some(rest) => rest
+            .split_once("__/")
+            .ok_or_else(|| error("expected a path like __modname__/path/file."))?,
+        None => (
+            state
+                .running
+                .last()
+                .map(String::as_str)
+                .ok_or_else(|| error("no mod is running to resolve it in."))?,
+            name,
+        ),
+    };
+    let mod_directory = state
+        .mods
+        .get(mod_name)
+        .ok_or_else(|| error(&format!("there is no mod named {mod_name}.")))?;
+
+    // `..`, absolute paths and doubled separators all leave an empty component
+    let module = module.strip_suffix(".lua").unwrap_or(module);
+    let components: Vec<&str> = module.split(['/', '.']).collect();
+    if components
+        .iter()
+        .any(|component| component.is_empty() || component.contains(['\\', ':']))
+    {
+        return Err(error(
+            "module paths can only go down into the mod's directory.",
+        ));
+    }
+    let file = format!("{}.lua", components.join("/"));
+    let path = mod_directory.join(&file);
+
+    // symlinks could still point out of the mod
+    let inside_mod = match (mod_directory.canonicalize(), path.canonicalize()) {
+        (Ok(mod_directory), Ok(path)) => path.starts_with(mod_directory),
+        (_, Err(_)) => return Err(error(&format!("{mod_name} has no file {file}."))),
+        (Err(_), _) => false,
+    };
+    if !inside_mod {
+        return Err(error(
+            "module paths can only go down into the mod's directory.",
+        ));
+    }
+    Ok((mod_name.to_string(), file, path))
+}
+
+#[test]
+fn require_loads_modules_once_and_stays_inside_mods() {
+    let lua = Lua::new();
+    install(
+        &lua,
+        [
+            ("core", PathBuf::from("assets/mods/core")),
+            ("base", PathBuf::from("assets/mods/base")),
+        ],
+    )
+    .expect("Could not install require");
+
+    let script = r#"
+        local serpent = require("__core__/lib/serpent")
+        assert(type(serpent.line) == "function")
+        assert(rawequal(serpent, require("__core__/lib.serpent.lua")))
+        assert(not pcall(require, "lib/serpent"))
+        assert(not pcall(require, "../core/lib/serpent"))
+        assert(not pcall(require, "__core__/../base/data"))
+        assert(not pcall(require, "__missing__/data"))
+    "#;
+    run_in_mod(&lua, "base", "test.lua", script.to_string()).expect("Script failed");
+    run_in_mod(
+        &lua,
+        "core",
+        "test.lua",
+        r#"assert(rawequal(require("lib/serpent"), require("__core__/lib/serpent")))"#.to_string(),
+    )
+    .expect("Script failed");
+}
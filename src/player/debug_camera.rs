@@ -1,7 +1,14 @@
+use std::collections::BTreeMap;
+
+use anyhow::{Result, anyhow, ensure};
 use bevy::input::mouse::MouseMotion;
 use bevy::prelude::*;
 use bevy::window::{CursorGrabMode, PrimaryWindow};
 
+use crate::console::commands::{AddConsoleCommand, ConsoleCommand, parse_arg};
+use crate::console::console_ui::Console;
+use crate::mod_manager::prototypes::{BlockPrototype, BlockPrototypes, Prototypes};
+
 pub mod prelude {
     pub use crate::*;
 }
@@ -51,8 +58,23 @@ impl Default for KeyBindings {
 /// Used in queries when you want flycams and not other cameras
 /// A marker component used in queries when you want flycams and not other cameras
 #[derive(Component)]
+#[require(Inventory)]
 pub struct FlyCam;
 
+/// Blocks given to a flycam with the `give` command, and how many of each.
+/// Nothing takes blocks out of it yet.
+#[derive(Component, Default, Debug)]
+pub struct Inventory(pub BTreeMap<&'static str, u32>);
+
+impl Inventory {
+    /// Adds `count` of `block`, returning how many there are now.
+    pub fn add(&mut self, block: &'static BlockPrototype, count: u32) -> u32 {
+        let held = self.0.entry(&*block.name).or_default();
+        *held = held.saturating_add(count);
+        *held
+    }
+}
+
 /// Grabs/ungrabs mouse cursor
 fn toggle_grab_cursor(window: &mut Window) {
     if window.cursor_options.grab_mode == CursorGrabMode::None {
@@ -81,8 +103,13 @@ fn player_move(
     primary_window: Query<&Window, With<PrimaryWindow>>,
     settings: Res<MovementSettings>,
     key_bindings: Res<KeyBindings>,
+    console: Option<Res<Console>>,
     mut query: Query<(&FlyCam, &mut Transform)>, //    mut query: Query<&mut Transform, With<FlyCam>>,
 ) {
+    if console.is_some_and(|console| console.is_open()) {
+        return;
+    }
+
     if let Ok(window) = primary_window.single() {
         for (_camera, mut transform) in &mut query {
             let mut velocity = Vec3::ZERO;
@@ -124,8 +151,15 @@ fn player_look(
     settings: Res<MovementSettings>,
     primary_window: Query<&Window, With<PrimaryWindow>>,
     mut state: EventReader<MouseMotion>,
+    console: Option<Res<Console>>,
     mut query: Query<&mut Transform, With<FlyCam>>,
 ) {
+    if console.is_some_and(|console| console.is_open()) {
+        // otherwise the camera jumps by everything the mouse moved once the console closes
+        state.clear();
+        return;
+    }
+
     if let Ok(window) = primary_window.single() {
         for mut transform in &mut query {
             for ev in state.read() {
@@ -153,8 +187,13 @@ fn player_look(
 fn cursor_grab(
     keys: Res<ButtonInput<KeyCode>>,
     key_bindings: Res<KeyBindings>,
+    console: Option<Res<Console>>,
     mut primary_window: Query<&mut Window, With<PrimaryWindow>>,
 ) {
+    if console.is_some_and(|console| console.is_open()) {
+        return;
+    }
+
     if let Ok(mut window) = primary_window.single_mut() {
         if keys.just_pressed(key_bindings.toggle_grab_cursor) {
             toggle_grab_cursor(&mut window);
@@ -191,6 +230,88 @@ impl Plugin for NoCameraPlayerPlugin {
             .add_systems(Startup, initial_grab_on_flycam_spawn)
             .add_systems(Update, player_move)
             .add_systems(Update, player_look)
-            .add_systems(Update, cursor_grab);
+            .add_systems(Update, cursor_grab)
+            .add_console_command(TeleportCommand)
+            .add_console_command(GiveCommand);
+    }
+}
+
+/// `tp <x> <y> <z>` moves every flycam to the given block position.
+struct TeleportCommand;
+
+impl ConsoleCommand for TeleportCommand {
+    fn name(&self) -> &'static str {
+        "tp"
+    }
+
+    fn usage(&self) -> &'static str {
+        "tp <x> <y> <z>"
+    }
+
+    fn run(&self, args: &[&str], world: &mut World) -> Result<String> {
+        let destination = Vec3::new(
+            parse_arg(args, 0, "x")?,
+            parse_arg(args, 1, "y")?,
+            parse_arg(args, 2, "z")?,
+        );
+        ensure!(destination.is_finite(), "Expected a finite position.");
+
+        let mut cameras = world.query_filtered::<&mut Transform, With<FlyCam>>();
+        for mut transform in cameras.iter_mut(world) {
+            transform.translation = destination;
+        }
+        Ok(format!("Teleported to {destination}"))
+    }
+}
+
+/// `give <block> [count]` adds blocks to the inventory of every flycam.
+struct GiveCommand;
+
+impl ConsoleCommand for GiveCommand {
+    fn name(&self) -> &'static str {
+        "give"
+    }
+
+    fn usage(&self) -> &'static str {
+        "give <block> [count]"
+    }
+
+    fn run(&self, args: &[&str], world: &mut World) -> Result<String> {
+        let block_name = args
+            .first()
+            .ok_or_else(|| anyhow!("Missing argument <block>."))?;
+        let block = world
+            .get_resource::<BlockPrototypes>()
+            .and_then(|block_prototypes| block_prototypes.get(block_name))
+            .ok_or_else(|| anyhow!("Unknown block `{block_name}`."))?;
+        let count: u32 = if args.len() > 1 {
+            parse_arg(args, 1, "count")?
+        } else {
+            1
+        };
+        ensure!(count > 0, "Expected a count above 0.");
+
+        let mut inventories = world.query_filtered::<&mut Inventory, With<FlyCam>>();
+        let mut held = None;
+        for mut inventory in inventories.iter_mut(world) {
+            held = Some(inventory.add(block, count));
+        }
+        let held = held.ok_or_else(|| anyhow!("There is no player to give blocks to."))?;
+        Ok(format!("Gave {count} {block_name}, now holding {held}."))
+    }
+
+    fn complete(&self, args: &[&str], world: &World) -> Vec<String> {
+        if !args.is_empty() {
+            return vec![];
+        }
+        world
+            .get_resource::<BlockPrototypes>()
+            .map(|block_prototypes| {
+                block_prototypes
+                    .iter()
+                    .map(|(name, _)| (*name).to_string())
+                    .collect()
+            })
+            .unwrap_or_default()
     }
 }
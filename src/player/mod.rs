@@ -1,2 +1,3 @@
 pub mod debug_camera;
 pub mod render_distance;
+pub mod spawn;
@@ -1,26 +1,42 @@
 /*!
 scanner is responsible for identifying what chunks needs to be loaded (mesh/data)
-the current implementation is exellent for low render distances, 1-15
-but anything above that might induce some frame lag, due to how the load/unload data is calculated.
-`scanner::new()` can also be very slow on high render distances, giving an initial slow execution time.
+on moving into a new chunk, only the chunks entering or leaving its areas are queued to be checked.
+the checks are spread over multiple frames, limited by `SCAN_TIME_BUDGET`,
+and are done against the scanner's area when resolved, so stale entries cost nothing but a skip.
+`Scanner::new()` builds every offset in the render distance, which gets slow on high render distances.
+`Scanner::set_render_distance` avoids this at runtime by computing the new offsets in the background.
 */
 
 use std::collections::VecDeque;
+use std::time::{Duration, Instant};
 
+use anyhow::{Result, ensure};
+use bevy::ecs::{component::HookContext, world::DeferredWorld};
 use bevy::platform::collections::HashSet;
 use bevy::prelude::*;
+use bevy::tasks::{AsyncComputeTaskPool, Task, block_on};
+use futures_lite::future;
 
 use crate::chunky::async_chunkloader::Chunks;
-use crate::chunky::chunks_refs::ChunkRefs;
-use crate::render::chunk_material::RenderableChunk;
-use crate::{position::ChunkPosition};
+use crate::chunky::chunk_interest::ChunkInterest;
+use crate::chunky::chunk_status::ChunkStatus;
+use crate::console::commands::{AddConsoleCommand, ConsoleCommand, parse_arg};
+use crate::utils::index_to_ivec3_bounds;
+use crate::position::{ChunkPosition, Position};
 
 use crate::chunky::{async_chunkloader::AsyncChunkloader, chunk::CHUNK_SIZE_I32};
 
-pub const MAX_DATA_TASKS: usize = 9;
-pub const MAX_MESH_TASKS: usize = 3;
+/// How long each scan system may spend resolving queued chunks per frame, split between the scanners.
+pub const SCAN_TIME_BUDGET: Duration = Duration::from_micros(500);
 
-pub const MAX_SCANS: usize = 26000;
+/// The largest render distance accepted by the `renderdistance` command.
+pub const MAX_RENDER_DISTANCE: u32 = 64;
+
+/// How many chunks past the render distance chunks are kept loaded by default.
+pub const DEFAULT_UNLOAD_MARGIN: u32 = 2;
+
+/// How quickly `Scanner::velocity` follows the actual movement. Higher is less smooth.
+pub const VELOCITY_SMOOTHING: f32 = 8.;
 
 pub struct ScannerPlugin;
 
@@ -29,6 +45,8 @@ impl Plugin for ScannerPlugin {
         app.add_systems(
             PreUpdate,
             (
+                apply_render_distance,
+                track_velocity,
                 detect_move,
                 scan_data,
                 scan_data_unload,
@@ -36,25 +54,137 @@ impl Plugin for ScannerPlugin {
                 scan_mesh,
             ),
         );
+        app.add_console_command(RenderDistanceCommand);
     }
 }
 
+/// Loads and meshes the chunks around its entity. Any number of scanners may exist at once.
+/// A chunk is only unloaded once no scanner keeps it.
 #[derive(Component)]
+#[component(on_remove = release_scanner_chunks)]
 pub struct Scanner {
-    pub prev_chunk_pos: ChunkPosition,
+    /// The chunk the scanner was in last frame. `None` until the first `detect_move`.
+    pub prev_chunk_pos: Option<ChunkPosition>,
+
+    pub horizontal_distance: u32,
+    pub vertical_distance: u32,
+    /// How many chunks further than the render distance a chunk must be before it is unloaded.
+    /// This stops chunks on the border from reloading whenever the scanner wobbles between two chunks.
+    pub unload_margin: u32,
 
-    // chunk positions we are yet to check we need need to load
-    pub unresolved_data_load: Vec<ChunkPosition>,
-    pub unresolved_mesh_load: Vec<ChunkPosition>,
+    /// Smoothed movement in blocks per second. Used to load chunks along the flight path first.
+    pub velocity: Vec3,
+    prev_translation: Option<Vec3>,
+
+    // chunk positions we are yet to check we need need to load.
+    // these are checked against the scanner's area when resolved, so stale entries are harmless.
+    pub unresolved_data_load: VecDeque<ChunkPosition>,
+    pub unresolved_mesh_load: VecDeque<ChunkPosition>,
 
     // chunk positions we are yet to check we need need tounload
     pub unresolved_data_unload: VecDeque<ChunkPosition>,
     pub unresolved_mesh_unload: VecDeque<ChunkPosition>,
 
+    // chunks this scanner counts towards in `ChunkInterest`
+    held_data: HashSet<ChunkPosition>,
+    held_mesh: HashSet<ChunkPosition>,
+
     // on detecting a scanner move, these offsets are used to
     // identify the location of what chunks need to be checked
-    pub worldgen_sampling_offsets: Vec<ChunkPosition>,
-    pub mesh_sampling_offsets: Vec<ChunkPosition>,
+    pub sampling_offsets: SamplingOffsets,
+
+    // offsets for a new render distance, being computed in the background
+    resize_task: Option<Task<SamplingOffsetsChange>>,
+}
+
+/// The cylinder of chunk offsets built by `make_offset_vec`, testable without a lookup.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct ScanArea {
+    pub horizontal_radius: i32,
+    pub vertical_radius: i32,
+}
+
+impl ScanArea {
+    #[must_use]
+    pub const fn from_diameters(horizontal_diameter: u32, vertical_diameter: u32) -> Self {
+        Self {
+            horizontal_radius: horizontal_diameter as i32 / 2,
+            vertical_radius: vertical_diameter as i32 / 2,
+        }
+    }
+
+    #[must_use]
+    pub const fn contains(self, offset: ChunkPosition) -> bool {
+        let Self {
+            horizontal_radius: radius,
+            vertical_radius,
+        } = self;
+        let IVec3 { x, y, z } = offset.0;
+        -radius <= x
+            && x < radius
+            && -radius <= z
+            && z < radius
+            && x * x + z * z <= radius * radius
+            && -vertical_radius <= y
+            && y < vertical_radius
+    }
+}
+
+/// Offsets from the scanner's chunk position that are inside its load and unload areas.
+#[derive(Clone, Default)]
+pub struct SamplingOffsets {
+    pub worldgen: Vec<ChunkPosition>,
+    pub mesh: Vec<ChunkPosition>,
+    /// chunks are kept loaded until they leave these larger areas
+    pub worldgen_unload: Vec<ChunkPosition>,
+    pub mesh_unload: Vec<ChunkPosition>,
+
+    pub worldgen_area: ScanArea,
+    pub mesh_area: ScanArea,
+    pub worldgen_unload_area: ScanArea,
+    pub mesh_unload_area: ScanArea,
+}
+
+impl SamplingOffsets {
+    /// construct the offsets for a render distance.
+    /// warning: slow execution time on distances above 30-40,
+    #[must_use]
+    pub fn new(horizontal_distance: u32, vertical_distance: u32, unload_margin: u32) -> Self {
+        // every generation stage after terrain needs its neighbours, so each one adds a ring of chunks
+        // that must be generated around the meshed area. the offsets are diameters, so a ring is 2 chunks.
+        let generation_margin = ChunkStatus::GENERATION_MARGIN * 2;
+        let worldgen_horizontal = horizontal_distance + generation_margin;
+        let worldgen_vertical = vertical_distance + generation_margin;
+        // the offsets are diameters, so the margin is applied on both sides.
+        let margin = unload_margin * 2;
+
+        let worldgen_area = ScanArea::from_diameters(worldgen_horizontal, worldgen_vertical);
+        let mesh_area = ScanArea::from_diameters(horizontal_distance, vertical_distance);
+        let worldgen_unload_area =
+            ScanArea::from_diameters(worldgen_horizontal + margin, worldgen_vertical + margin);
+        let mesh_unload_area =
+            ScanArea::from_diameters(horizontal_distance + margin, vertical_distance + margin);
+
+        Self {
+            worldgen: make_offset_vec(worldgen_area),
+            mesh: make_offset_vec(mesh_area),
+            worldgen_unload: make_offset_vec(worldgen_unload_area),
+            mesh_unload: make_offset_vec(mesh_unload_area),
+            worldgen_area,
+            mesh_area,
+            worldgen_unload_area,
+            mesh_unload_area,
+        }
+    }
+}
+
+/// The sampling offsets of a resized scanner, and how they differ from the current ones.
+struct SamplingOffsetsChange {
+    sampling_offsets: SamplingOffsets,
+    data_load: Vec<ChunkPosition>,
+    data_unload: Vec<ChunkPosition>,
+    mesh_load: Vec<ChunkPosition>,
+    mesh_unload: Vec<ChunkPosition>,
 }
 
 impl Scanner {
@@ -62,133 +192,266 @@ impl Scanner {
     /// warning: slow execution time on distances above 30-40,
     #[must_use]
     pub fn new(distance: u32) -> Self {
-        let mesh_distance = distance;
-        // This is +1 becuase meshes require all adjacent chunks loaded in a 3x3x3 area before they can be meshed.
-        let worldgen_distance = distance + 1;
+        Self::with_distances(distance, distance, DEFAULT_UNLOAD_MARGIN)
+    }
 
+    /// construct scanner with a different horizontal and vertical distance
+    /// warning: slow execution time on distances above 30-40,
+    #[must_use]
+    pub fn with_distances(horizontal_distance: u32, vertical_distance: u32, unload_margin: u32) -> Self {
         Self {
-            worldgen_sampling_offsets: make_offset_vec(worldgen_distance),
-            mesh_sampling_offsets: make_offset_vec(mesh_distance),
-            unresolved_data_load: Vec::default(),
-            prev_chunk_pos: ChunkPosition::new(777, 777, 777),
-            unresolved_mesh_load: Vec::default(),
+            sampling_offsets: SamplingOffsets::new(horizontal_distance, vertical_distance, unload_margin),
+            horizontal_distance,
+            vertical_distance,
+            unload_margin,
+            velocity: Vec3::ZERO,
+            prev_translation: None,
+            unresolved_data_load: VecDeque::default(),
+            prev_chunk_pos: None,
+            unresolved_mesh_load: VecDeque::default(),
             unresolved_data_unload: VecDeque::default(),
             unresolved_mesh_unload: VecDeque::default(),
+            held_data: HashSet::default(),
+            held_mesh: HashSet::default(),
+            resize_task: None,
         }
     }
+
+    /// Changes the render distance without resetting the scanner.
+    /// The new sampling offsets are computed in the background. Once they are ready,
+    /// only the chunks that enter or leave the render distance are queued for load or unload.
+    /// Calling this again before the previous resize finished replaces it.
+    pub fn set_render_distance(&mut self, horizontal_distance: u32, vertical_distance: u32) {
+        self.horizontal_distance = horizontal_distance;
+        self.vertical_distance = vertical_distance;
+        self.resize();
+    }
+
+    /// Changes the unload margin without resetting the scanner. See [`Scanner::set_render_distance`].
+    pub fn set_unload_margin(&mut self, unload_margin: u32) {
+        self.unload_margin = unload_margin;
+        self.resize();
+    }
+
+    /// True if the chunk is inside the area this scanner generates.
+    #[must_use]
+    pub fn wants_data(&self, chunk_pos: ChunkPosition) -> bool {
+        self.contains(self.sampling_offsets.worldgen_area, chunk_pos)
+    }
+
+    /// True if the chunk is inside the area this scanner keeps loaded.
+    #[must_use]
+    pub fn keeps_data(&self, chunk_pos: ChunkPosition) -> bool {
+        self.contains(self.sampling_offsets.worldgen_unload_area, chunk_pos)
+    }
+
+    /// True if the chunk is inside the area this scanner meshes.
+    #[must_use]
+    pub fn wants_mesh(&self, chunk_pos: ChunkPosition) -> bool {
+        self.contains(self.sampling_offsets.mesh_area, chunk_pos)
+    }
+
+    /// True if the chunk is inside the area this scanner keeps meshed.
+    #[must_use]
+    pub fn keeps_mesh(&self, chunk_pos: ChunkPosition) -> bool {
+        self.contains(self.sampling_offsets.mesh_unload_area, chunk_pos)
+    }
+
+    fn contains(&self, area: ScanArea, chunk_pos: ChunkPosition) -> bool {
+        self.prev_chunk_pos
+            .is_some_and(|center| area.contains(chunk_pos - center))
+    }
+
+    fn resize(&mut self) {
+        let horizontal_distance = self.horizontal_distance;
+        let vertical_distance = self.vertical_distance;
+        let unload_margin = self.unload_margin;
+
+        // the diff is made against the offsets in use when the task completes, which are the current ones.
+        let old = self.sampling_offsets.clone();
+        let task = AsyncComputeTaskPool::get().spawn(async move {
+            let new = SamplingOffsets::new(horizontal_distance, vertical_distance, unload_margin);
+            let data_load = offsets_outside(&new.worldgen, old.worldgen_area);
+            let data_unload = offsets_outside(&old.worldgen_unload, new.worldgen_unload_area);
+            let mesh_load = offsets_outside(&new.mesh, old.mesh_area);
+            let mesh_unload = offsets_outside(&old.mesh_unload, new.mesh_unload_area);
+            SamplingOffsetsChange {
+                sampling_offsets: new,
+                data_load,
+                data_unload,
+                mesh_load,
+                mesh_unload,
+            }
+        });
+        self.resize_task = Some(task);
+    }
+}
+
+/// Gives up everything a despawned scanner held, unloading the chunks no other scanner wants.
+fn release_scanner_chunks(mut world: DeferredWorld, context: HookContext) {
+    let Some(mut scanner) = world.get_mut::<Scanner>(context.entity) else {
+        return;
+    };
+    let held_data = std::mem::take(&mut scanner.held_data);
+    let held_mesh = std::mem::take(&mut scanner.held_mesh);
+
+    let mut interest = world.resource_mut::<ChunkInterest>();
+    let data_unload: Vec<ChunkPosition> = held_data
+        .into_iter()
+        .filter(|chunk_pos| interest.release_data(*chunk_pos))
+        .collect();
+    let mesh_unload: Vec<ChunkPosition> = held_mesh
+        .into_iter()
+        .filter(|chunk_pos| interest.release_mesh(*chunk_pos))
+        .collect();
+
+    let mut chunkloader = world.resource_mut::<AsyncChunkloader>();
+    for chunk_pos in data_unload {
+        chunkloader.cancel_chunk_load(chunk_pos);
+        chunkloader.unload_chunk_queue.insert(chunk_pos);
+    }
+    for chunk_pos in mesh_unload {
+        chunkloader.cancel_mesh(chunk_pos);
+        chunkloader.unload_mesh_queue.insert(chunk_pos);
+    }
+}
+
+/// The chunk a scanner is considered to be in.
+/// The scan offsets reach one chunk further in negative directions, so this is offset by half a chunk
+/// to keep the scanned area centred on the scanner.
+fn scanner_chunk_position(g_transform: &GlobalTransform) -> ChunkPosition {
+    let position = g_transform.translation().floor().as_ivec3() - IVec3::splat(CHUNK_SIZE_I32 / 2);
+    ChunkPosition::from(Position(position))
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn track_velocity(mut scanners: Query<(&mut Scanner, &GlobalTransform)>, time: Res<Time>) {
+    let delta = time.delta_secs();
+    if delta <= 0. {
+        return;
+    }
+    for (mut scanner, g_transform) in &mut scanners {
+        let translation = g_transform.translation();
+        let measured = scanner
+            .prev_translation
+            .map_or(Vec3::ZERO, |prev_translation| (translation - prev_translation) / delta);
+        let smoothing = 1. - (-VELOCITY_SMOOTHING * delta).exp();
+        scanner.velocity = scanner.velocity.lerp(measured, smoothing);
+        scanner.prev_translation = Some(translation);
+    }
 }
 
 /// on scanner chunk change, enqueue chunks to load/unload
-fn detect_move(
-    mut scanners: Query<(&mut Scanner, &GlobalTransform)>,
-    mut chunkloader: ResMut<AsyncChunkloader>,
-) {
+fn detect_move(mut scanners: Query<(&mut Scanner, &GlobalTransform)>) {
     for (mut scanner, g_transform) in &mut scanners {
-        let chunk_pos = (g_transform.translation().as_ivec3() - IVec3::splat(CHUNK_SIZE_I32 / 2))
-            / CHUNK_SIZE_I32;
-        let chunk_pos = ChunkPosition(chunk_pos);
+        let chunk_pos = scanner_chunk_position(g_transform);
         let previous_chunk_pos = scanner.prev_chunk_pos;
-        let chunk_pos_changed = chunk_pos != scanner.prev_chunk_pos;
-        scanner.prev_chunk_pos = chunk_pos;
+        let chunk_pos_changed = Some(chunk_pos) != scanner.prev_chunk_pos;
+        scanner.prev_chunk_pos = Some(chunk_pos);
         if !chunk_pos_changed {
-            return;
+            continue;
         }
 
-        let load_data_area = scanner
-            .worldgen_sampling_offsets
-            .iter()
-            .map(|offset| chunk_pos + *offset)
-            .collect::<HashSet<ChunkPosition>>();
-
-        let unload_data_area = scanner
-            .worldgen_sampling_offsets
-            .iter()
-            .map(|offset| previous_chunk_pos + *offset)
-            .collect::<HashSet<ChunkPosition>>();
-
-        let load_mesh_area = scanner
-            .mesh_sampling_offsets
-            .iter()
-            .map(|offset| chunk_pos + *offset)
-            .collect::<HashSet<ChunkPosition>>();
-
-        let unload_mesh_area = scanner
-            .mesh_sampling_offsets
-            .iter()
-            .map(|offset| previous_chunk_pos + *offset)
-            .collect::<HashSet<ChunkPosition>>();
-
-        let data_load = load_data_area.difference(&unload_data_area);
-        let data_unload = unload_data_area.difference(&load_data_area);
-        let mesh_load = load_mesh_area.difference(&unload_mesh_area);
-        let mesh_unload = unload_mesh_area.difference(&load_mesh_area);
-
-        scanner.unresolved_data_load.extend(data_load);
-        scanner.unresolved_data_unload.extend(data_unload);
-        scanner.unresolved_mesh_unload.extend(mesh_unload);
-        scanner.unresolved_mesh_load.extend(mesh_load);
-
         // deconstruct scanner mutable references because rust :P
         let Scanner {
             unresolved_data_load,
             unresolved_mesh_load,
             unresolved_data_unload,
             unresolved_mesh_unload,
+            sampling_offsets: offsets,
             ..
-        } = scanner.as_mut();
-
-        for p in unresolved_mesh_unload.iter() {
-            if let Some((i, _)) = chunkloader
-                .load_mesh_queue
-                .iter()
-                .enumerate()
-                .find(|(_i, k)| *k == p)
-            {
-                chunkloader.load_mesh_queue.remove(i);
-            }
-        }
-        for p in unresolved_data_unload.iter() {
-            if let Some((i, _)) = chunkloader
-                .load_chunk_queue
-                .iter()
-                .enumerate()
-                .find(|(_i, k)| *k == p)
-            {
-                chunkloader.load_chunk_queue.remove(i);
-            }
+        } = &mut *scanner;
+
+        // chunks load when they enter the load area, but only unload when they leave the larger unload area.
+        unresolved_data_load.extend(outside_of(
+            &offsets.worldgen,
+            offsets.worldgen_area,
+            chunk_pos,
+            previous_chunk_pos,
+        ));
+        unresolved_mesh_load.extend(outside_of(
+            &offsets.mesh,
+            offsets.mesh_area,
+            chunk_pos,
+            previous_chunk_pos,
+        ));
+        if let Some(previous_chunk_pos) = previous_chunk_pos {
+            unresolved_data_unload.extend(outside_of(
+                &offsets.worldgen_unload,
+                offsets.worldgen_unload_area,
+                previous_chunk_pos,
+                Some(chunk_pos),
+            ));
+            unresolved_mesh_unload.extend(outside_of(
+                &offsets.mesh_unload,
+                offsets.mesh_unload_area,
+                previous_chunk_pos,
+                Some(chunk_pos),
+            ));
         }
+    }
+}
 
-        // remove the unloads from load
-        unresolved_mesh_load.retain(|p| {
-            let want_unload = unresolved_mesh_unload.contains(p);
-            !want_unload
-        });
-        // remove the unloads from load
-        unresolved_data_load.retain(|p| {
-            let want_unload = unresolved_data_unload.contains(p);
-            !want_unload
-        });
+/// positions in `offsets` around `from` that are outside of `area` around `to`.
+/// the offsets are sorted by distance, so the result is too.
+fn outside_of(
+    offsets: &[ChunkPosition],
+    area: ScanArea,
+    from: ChunkPosition,
+    to: Option<ChunkPosition>,
+) -> impl Iterator<Item = ChunkPosition> + '_ {
+    offsets
+        .iter()
+        .map(move |offset| from + *offset)
+        .filter(move |position| to.is_none_or(|to| !area.contains(*position - to)))
+}
 
-        scanner.unresolved_mesh_load.sort_by(|a, b| {
-            a.0.distance_squared(chunk_pos.0)
-                .cmp(&b.0.distance_squared(chunk_pos.0))
-        });
-        scanner.unresolved_data_load.sort_by(|a, b| {
-            a.0.distance_squared(chunk_pos.0)
-                .cmp(&b.0.distance_squared(chunk_pos.0))
-        });
+/// swaps in the sampling offsets of scanners that finished resizing
+fn apply_render_distance(mut scanners: Query<&mut Scanner>) {
+    for mut scanner in &mut scanners {
+        let Some(task) = &mut scanner.resize_task else {
+            continue;
+        };
+        let Some(change) = block_on(future::poll_once(task)) else {
+            continue;
+        };
+        scanner.resize_task = None;
+
+        let SamplingOffsetsChange {
+            sampling_offsets,
+            data_load,
+            data_unload,
+            mesh_load,
+            mesh_unload,
+        } = change;
+        scanner.sampling_offsets = sampling_offsets;
+
+        // a scanner that hasn't been placed yet will scan its whole area on the first `detect_move`.
+        let Some(chunk_pos) = scanner.prev_chunk_pos else {
+            continue;
+        };
+        let at = |offsets: Vec<ChunkPosition>| offsets.into_iter().map(move |offset| chunk_pos + offset);
+        scanner.unresolved_data_load.extend(at(data_load));
+        scanner.unresolved_data_unload.extend(at(data_unload));
+        scanner.unresolved_mesh_load.extend(at(mesh_load));
+        scanner.unresolved_mesh_unload.extend(at(mesh_unload));
     }
 }
 
-/// constructs a cylinder of chunk positions with the provided chunk radius
-fn make_offset_vec(diameter: u32) -> Vec<ChunkPosition> {
+/// returns the offsets that are not inside `area`
+fn offsets_outside(offsets: &[ChunkPosition], area: ScanArea) -> Vec<ChunkPosition> {
+    offsets.iter().filter(|offset| !area.contains(**offset)).copied().collect()
+}
+
+/// constructs every chunk offset inside `area`, sorted by distance
+fn make_offset_vec(area: ScanArea) -> Vec<ChunkPosition> {
     let mut sampling_offsets = vec![];
-    let diameter = diameter as i32;
-    let radius = diameter / 2;
+    let radius = area.horizontal_radius;
+    let vertical_radius = area.vertical_radius;
     for x in -radius..radius {
         for z in -radius..radius {
             if IVec2::new(x, z).distance_squared(IVec2::ZERO) <= radius * radius {
-                for y in -radius..radius {
+                for y in -vertical_radius..vertical_radius {
                     sampling_offsets.push(ChunkPosition::new(x, y, z));
                 }
             }
@@ -203,34 +466,78 @@ fn make_offset_vec(diameter: u32) -> Vec<ChunkPosition> {
     sampling_offsets
 }
 
+/// Splits the time a scan system may run in a single frame between the scanners.
+/// Time a scanner doesn't use is passed on to the scanners after it.
+struct ScanFrame {
+    start: Instant,
+    scanners_left: u32,
+}
+
+impl ScanFrame {
+    fn new(scanners: usize) -> Self {
+        Self {
+            start: Instant::now(),
+            scanners_left: u32::try_from(scanners).unwrap_or(u32::MAX),
+        }
+    }
+
+    /// The budget of the next scanner.
+    fn next_scanner(&mut self) -> ScanBudget {
+        let remaining = SCAN_TIME_BUDGET.saturating_sub(self.start.elapsed());
+        let limit = remaining / self.scanners_left.max(1);
+        self.scanners_left = self.scanners_left.saturating_sub(1);
+        ScanBudget {
+            start: Instant::now(),
+            limit,
+            scans: 0,
+        }
+    }
+}
+
+/// Limits how long one scanner may be resolved for.
+/// Checking the clock is not free, so it is only read every few scans.
+struct ScanBudget {
+    start: Instant,
+    limit: Duration,
+    scans: u32,
+}
+
+impl ScanBudget {
+    const SCANS_PER_CLOCK_CHECK: u32 = 256;
+
+    fn exhausted(&mut self) -> bool {
+        self.scans += 1;
+        self.scans % Self::SCANS_PER_CLOCK_CHECK == 0 && self.start.elapsed() >= self.limit
+    }
+}
+
 #[allow(clippy::needless_pass_by_value)]
 pub fn scan_data(
-    mut scanners: Query<(&mut Scanner, &GlobalTransform)>,
+    mut scanners: Query<&mut Scanner>,
     mut chunkloader: ResMut<AsyncChunkloader>,
+    mut interest: ResMut<ChunkInterest>,
     chunks: Res<Chunks>,
 ) {
-    for (mut scanner, _g_transform) in &mut scanners {
-        if chunkloader.worldgen_tasks.len() >= MAX_DATA_TASKS {
-            return;
-        }
-        let l = scanner.unresolved_data_load.len();
-        // for chunk_pos in scanner.unresolved_data_load.drain(..) {
-        for chunk_pos in scanner.unresolved_data_load.drain(0..MAX_SCANS.min(l)) {
-            // want to load chunk
+    let mut frame = ScanFrame::new(scanners.iter().count());
+    for mut scanner in &mut scanners {
+        let mut budget = frame.next_scanner();
+        while !budget.exhausted() {
+            let Some(chunk_pos) = scanner.unresolved_data_load.pop_front() else {
+                break;
+            };
+            if !scanner.wants_data(chunk_pos) {
+                continue;
+            }
+            if scanner.held_data.insert(chunk_pos) {
+                interest.hold_data(chunk_pos);
+            }
             let is_busy = chunks.0.contains_key(&chunk_pos)
-                || chunkloader.load_chunk_queue.contains(&chunk_pos)
-                || chunkloader.worldgen_tasks.contains_key(&chunk_pos);
-            if !is_busy {
-                chunkloader.load_chunk_queue.push(chunk_pos);
+                || chunkloader.worldgen_tasks.contains(chunk_pos);
+            if is_busy {
                 // abort unload
-                let index_of_unloading = chunkloader
-                    .unload_chunk_queue
-                    .iter()
-                    .enumerate()
-                    .find_map(|(i, pos)| if pos == &chunk_pos { Some(i) } else { None });
-                if let Some(i) = index_of_unloading {
-                    chunkloader.unload_chunk_queue.remove(i);
-                }
+                chunkloader.unload_chunk_queue.remove(&chunk_pos);
+            } else {
+                chunkloader.queue_chunk_load(chunk_pos);
             }
         }
     }
@@ -238,30 +545,55 @@ pub fn scan_data(
 
 #[allow(clippy::needless_pass_by_value)]
 pub fn scan_data_unload(
-    mut scanners: Query<(&mut Scanner, &GlobalTransform)>,
+    mut scanners: Query<&mut Scanner>,
     mut chunkloader: ResMut<AsyncChunkloader>,
+    mut interest: ResMut<ChunkInterest>,
     chunks: Res<Chunks>,
 ) {
-    // find all loaded and check if in range
-    for (mut scanner, _g_transform) in &mut scanners {
-        for chunk_pos in scanner.unresolved_data_unload.drain(..) {
-            // want to load chunk
-            let is_busy = !chunks.0.contains_key(&chunk_pos);
-            if !is_busy {
-                chunkloader.unload_chunk_queue.push(chunk_pos);
+    let mut frame = ScanFrame::new(scanners.iter().count());
+    for mut scanner in &mut scanners {
+        let mut budget = frame.next_scanner();
+        while !budget.exhausted() {
+            let Some(chunk_pos) = scanner.unresolved_data_unload.pop_front() else {
+                break;
+            };
+            if scanner.keeps_data(chunk_pos) || !scanner.held_data.remove(&chunk_pos) {
+                continue;
+            }
+            // another scanner still wants it
+            if !interest.release_data(chunk_pos) {
+                continue;
+            }
+            chunkloader.cancel_chunk_load(chunk_pos);
+            // chunks still generating are unloaded too, so that their task is cancelled.
+            if chunks.0.contains_key(&chunk_pos) || chunkloader.worldgen_tasks.contains(chunk_pos) {
+                chunkloader.unload_chunk_queue.insert(chunk_pos);
             }
         }
     }
 }
 
+#[allow(clippy::needless_pass_by_value)]
 pub fn scan_mesh_unload(
     mut scanners: Query<&mut Scanner>,
     mut chunkloader: ResMut<AsyncChunkloader>,
+    mut interest: ResMut<ChunkInterest>,
 ) {
-    // find all loaded and check if in range
+    let mut frame = ScanFrame::new(scanners.iter().count());
     for mut scanner in &mut scanners {
-        for chunk_pos in scanner.unresolved_mesh_unload.drain(..) {
-            chunkloader.unload_mesh_queue.push(chunk_pos);
+        let mut budget = frame.next_scanner();
+        while !budget.exhausted() {
+            let Some(chunk_pos) = scanner.unresolved_mesh_unload.pop_front() else {
+                break;
+            };
+            if scanner.keeps_mesh(chunk_pos) || !scanner.held_mesh.remove(&chunk_pos) {
+                continue;
+            }
+            if !interest.release_mesh(chunk_pos) {
+                continue;
+            }
+            chunkloader.cancel_mesh(chunk_pos);
+            chunkloader.unload_mesh_queue.insert(chunk_pos);
         }
     }
 }
@@ -270,50 +602,102 @@ pub fn scan_mesh_unload(
 pub fn scan_mesh(
     mut scanners: Query<&mut Scanner>,
     mut chunkloader: ResMut<AsyncChunkloader>,
-    chunks: Res<Chunks>,
+    mut interest: ResMut<ChunkInterest>,
 ) {
+    let mut frame = ScanFrame::new(scanners.iter().count());
     for mut scanner in &mut scanners {
-        // if chunkloader.worldgen_tasks.len() >= MAX_MESH_TASKS {
-        //     return;
-        // }
-        let mut retries = Vec::new();
-        let l = scanner.unresolved_mesh_load.len();
-        for chunk_position in scanner.unresolved_mesh_load.drain(0..MAX_SCANS.min(l)) {
-            let busy = chunkloader
-                .load_mesh_queue
-                .iter()
-                .any(|queued_chunk_refs| queued_chunk_refs.center_chunk_position == chunk_position);
+        let mut budget = frame.next_scanner();
+        // a chunk can only be meshed once its neighbours are decorated, so instead of retrying
+        // every frame, the chunks around each newly decorated chunk are checked again.
+        for &decorated in &chunkloader.recently_decorated {
+            for i in 0..27 {
+                let chunk_position = decorated + ChunkPosition(index_to_ivec3_bounds(i, 3) + IVec3::NEG_ONE);
+                if scanner.wants_mesh(chunk_position) {
+                    scanner.unresolved_mesh_load.push_back(chunk_position);
+                }
+            }
+        }
 
+        while !budget.exhausted() {
+            let Some(chunk_position) = scanner.unresolved_mesh_load.pop_front() else {
+                break;
+            };
+            if !scanner.wants_mesh(chunk_position) {
+                continue;
+            }
+            if scanner.held_mesh.insert(chunk_position) {
+                interest.hold_mesh(chunk_position);
+            }
+
+            // chunks kept meshed by the unload margin don't need a new mesh when they come back in range.
+            let busy = chunkloader.statuses.get(chunk_position) == ChunkStatus::Meshed
+                || chunkloader.mesh_tasks.contains(chunk_position)
+                || chunkloader.load_mesh_queue.contains(&chunk_position);
             if busy {
+                // abort unload
+                chunkloader.unload_mesh_queue.remove(&chunk_position);
                 continue;
             }
 
-            // all 27 adjacent voxel datas are available. we are safe to start a mesh thread.
-            let Some(adjacent_chunks) = ChunkRefs::try_new(&chunks, chunk_position) else {
-                retries.push(chunk_position);
+            // checked again when the missing neighbours are decorated.
+            if !chunkloader
+                .statuses
+                .neighbourhood_reached(chunk_position, ChunkStatus::Decorated)
+            {
                 continue;
-            };
+            }
+
+            chunkloader.unload_mesh_queue.remove(&chunk_position);
+            if chunkloader.restore_cached_mesh(chunk_position) {
+                continue;
+            }
+            chunkloader.queue_mesh(chunk_position);
+        }
+    }
+}
 
-            chunkloader.load_mesh_queue.push(adjacent_chunks);
-
-            // abort unload
-            let index_of_unloading =
-                chunkloader
-                    .unload_mesh_queue
-                    .iter()
-                    .enumerate()
-                    .find_map(|(i, pos)| {
-                        if pos == &chunk_position {
-                            Some(i)
-                        } else {
-                            None
-                        }
-                    });
-
-            if let Some(i) = index_of_unloading {
-                chunkloader.unload_mesh_queue.remove(i);
+/// `renderdistance <horizontal> [vertical] [unload margin]` resizes every scanner.
+struct RenderDistanceCommand;
+
+impl ConsoleCommand for RenderDistanceCommand {
+    fn name(&self) -> &'static str {
+        "renderdistance"
+    }
+
+    fn usage(&self) -> &'static str {
+        "renderdistance <horizontal chunks> [vertical chunks] [unload margin]"
+    }
+
+    fn run(&self, args: &[&str], world: &mut World) -> Result<String> {
+        let horizontal_distance: u32 = parse_arg(args, 0, "horizontal chunks")?;
+        let vertical_distance: u32 = if args.len() > 1 {
+            parse_arg(args, 1, "vertical chunks")?
+        } else {
+            horizontal_distance
+        };
+        for distance in [horizontal_distance, vertical_distance] {
+            ensure!(
+                (1..=MAX_RENDER_DISTANCE).contains(&distance),
+                "Expected a render distance between 1 and {MAX_RENDER_DISTANCE}, got {distance}."
+            );
+        }
+
+        let unload_margin: Option<u32> = if args.len() > 2 {
+            Some(parse_arg(args, 2, "unload margin")?)
+        } else {
+            None
+        };
+
+        let mut scanners = world.query::<&mut Scanner>();
+        for mut scanner in scanners.iter_mut(world) {
+            if let Some(unload_margin) = unload_margin {
+                scanner.unload_margin = unload_margin;
             }
+            scanner.set_render_distance(horizontal_distance, vertical_distance);
         }
-        scanner.unresolved_mesh_load.append(&mut retries);
+
+        Ok(format!(
+            "Render distance set to {horizontal_distance} horizontal, {vertical_distance} vertical"
+        ))
     }
 }
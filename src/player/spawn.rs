@@ -0,0 +1,39 @@
+//! Moves newly spawned players onto the ground once the world generator exists.
+
+use bevy::prelude::*;
+
+use crate::worldgen::generator::ActiveWorldGenerator;
+
+/// How far above the ground the camera of a spawned player is.
+pub const EYE_HEIGHT: f32 = 1.7;
+
+pub struct SpawnPlugin;
+
+impl Plugin for SpawnPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            spawn_on_ground.run_if(resource_exists::<ActiveWorldGenerator>),
+        );
+    }
+}
+
+/// Put this on an entity to move it onto the surface below it once the world generator exists.
+/// Its y is left alone if the generator doesn't know where the surface is.
+#[derive(Component, Default)]
+pub struct SpawnOnGround;
+
+#[allow(clippy::needless_pass_by_value)]
+fn spawn_on_ground(
+    mut commands: Commands,
+    generator: Res<ActiveWorldGenerator>,
+    mut spawning: Query<(Entity, &mut Transform), With<SpawnOnGround>>,
+) {
+    for (entity, mut transform) in &mut spawning {
+        let position = transform.translation.floor().as_ivec3();
+        if let Some(surface) = generator.surface_height(position.x, position.z) {
+            transform.translation.y = (surface + 1) as f32 + EYE_HEIGHT;
+        }
+        commands.entity(entity).remove::<SpawnOnGround>();
+    }
+}
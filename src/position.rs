@@ -9,12 +9,12 @@ use crate::chunky::chunk::CHUNK_SIZE_I32;
 pub struct Position(pub IVec3);
 
 /// A floating point position in the world.
-#[derive(Debug, Clone, Copy, Deref)]
+#[derive(Debug, Default, Clone, Copy, Deref)]
 pub struct FloatingPosition(pub Vec3);
 
 /// Represents the location of a chunk.
 /// The x, y, z components are scaled down by a factor of `chunk::CHUNK_SIZE`
-#[derive(Debug, Hash, Clone, Copy, PartialEq, Eq, Deref)]
+#[derive(Debug, Default, Hash, Clone, Copy, PartialEq, Eq, Deref)]
 pub struct ChunkPosition(pub IVec3);
 
 impl Position {
@@ -22,6 +22,12 @@ impl Position {
     pub const fn new(x: i32, y: i32, z: i32) -> Self {
         Self(IVec3 { x, y, z })
     }
+
+    /// The position relative to the corner of the chunk containing it.
+    #[must_use]
+    pub fn local_to_chunk(self) -> Self {
+        Self(self.0.rem_euclid(IVec3::splat(CHUNK_SIZE_I32)))
+    }
 }
 
 impl FloatingPosition {
@@ -40,11 +46,7 @@ impl ChunkPosition {
 
 impl From<Position> for ChunkPosition {
     fn from(position: Position) -> Self {
-        Self(IVec3 {
-            x: position.0.x / CHUNK_SIZE_I32,
-            y: position.0.y / CHUNK_SIZE_I32,
-            z: position.0.z / CHUNK_SIZE_I32,
-        })
+        Self(position.0.div_euclid(IVec3::splat(CHUNK_SIZE_I32)))
     }
 }
 
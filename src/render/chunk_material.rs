@@ -7,7 +7,13 @@
 //! implementation using bevy's low level rendering api.
 //! It's generally recommended to try the built-in instancing before going with this approach.
 
-use std::sync::{Arc, OnceLock};
+use std::{
+    sync::{
+        Arc, OnceLock,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
 
 use bevy::{
     prelude::*,
@@ -114,6 +120,19 @@ impl RenderableChunk {
     }
 }
 
+/// Time spent baking chunks in the render world since the last call to [`take_bake_time`].
+/// The render world runs in parallel with the main world, so this is shared through atomics.
+static BAKE_NANOS: AtomicU64 = AtomicU64::new(0);
+static BAKE_COUNT: AtomicU64 = AtomicU64::new(0);
+
+/// Returns the total time spent in `ChunkMaterial::bake` and how many chunks were baked,
+/// then resets both counters.
+pub fn take_bake_time() -> (Duration, u64) {
+    let nanos = BAKE_NANOS.swap(0, Ordering::Relaxed);
+    let count = BAKE_COUNT.swap(0, Ordering::Relaxed);
+    (Duration::from_nanos(nanos), count)
+}
+
 struct BakedChunkMaterial {
     instance_buffer: Buffer,
     instance_buffer_length: usize,
@@ -131,6 +150,7 @@ impl ChunkMaterial {
     #[inline]
     fn bake(&self, render_device: &RenderDevice) -> &BakedChunkMaterial {
         self.baked.get_or_init(|| {
+            let start = Instant::now();
             let instance_buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
                 label: Some("chunk per-instance data buffer"),
                 contents: bytemuck::cast_slice(&self.quads),
@@ -156,12 +176,16 @@ impl ChunkMaterial {
                 }],
             );
 
-            BakedChunkMaterial {
+            let baked = BakedChunkMaterial {
                 instance_buffer,
                 uniform_bind_group,
                 instance_buffer_length: self.quads.len(),
                 simple_quad: SimpleQuad::new(render_device),
-            }
+            };
+
+            BAKE_NANOS.fetch_add(start.elapsed().as_nanos() as u64, Ordering::Relaxed);
+            BAKE_COUNT.fetch_add(1, Ordering::Relaxed);
+            baked
         })
     }
 
@@ -1,7 +1,10 @@
 use std::time::Duration;
 
+use anyhow::{Result, bail, ensure};
 use bevy::prelude::*;
 
+use crate::console::commands::{AddConsoleCommand, ConsoleCommand, parse_arg};
+
 pub const DAY_TIME_SEC: f32 = 60.0;
 pub const NIGHT_TIME_SEC: f32 = 10.0;
 pub const CYCLE_TIME: f32 = DAY_TIME_SEC + NIGHT_TIME_SEC;
@@ -28,6 +31,45 @@ impl Plugin for SunPlugin {
             TimerMode::Repeating,
         )));
         app.add_systems(Update, daylight_cycle);
+        app.add_console_command(TimeCommand);
+    }
+}
+
+/// `time set <fraction>` where 0 is sunrise and 1 is the end of the night.
+struct TimeCommand;
+
+impl ConsoleCommand for TimeCommand {
+    fn name(&self) -> &'static str {
+        "time"
+    }
+
+    fn usage(&self) -> &'static str {
+        "time [set <fraction of day 0..1>]"
+    }
+
+    fn run(&self, args: &[&str], world: &mut World) -> Result<String> {
+        let mut sky_time = world.resource_mut::<SkyTime>();
+        match args.first() {
+            None => Ok(format!("time is {:.3}", sky_time.0 / CYCLE_TIME)),
+            Some(&"set") => {
+                let fraction: f32 = parse_arg(args, 1, "fraction")?;
+                ensure!(
+                    (0.0..=1.0).contains(&fraction),
+                    "Expected a fraction between 0 and 1, got {fraction}."
+                );
+                sky_time.0 = fraction * CYCLE_TIME;
+                Ok(format!("time set to {fraction:.3}"))
+            }
+            Some(other) => bail!("Unknown subcommand `{other}`."),
+        }
+    }
+
+    fn complete(&self, args: &[&str], _world: &World) -> Vec<String> {
+        if args.is_empty() {
+            vec!["set".to_string()]
+        } else {
+            vec![]
+        }
     }
 }
 
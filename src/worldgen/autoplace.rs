@@ -0,0 +1,270 @@
+//! Places patches of resources underground.
+//! Patches are scattered on a grid of cells much larger than a chunk, and each cell is generated
+//! only from the seed and its own position. A chunk looks up every cell whose patches could reach it,
+//! so a patch that crosses a chunk border is the same on both sides.
+
+use anyhow::Result;
+use bevy::prelude::*;
+use bracket_noise::prelude::*;
+use rand::Rng;
+
+use crate::{
+    chunky::chunk::CHUNK_SIZE_I32,
+    mod_manager::prototypes::{
+        AutoplaceSettings, BlockPrototype, BlockPrototypes, Prototypes, ResourcePrototype,
+        ResourcePrototypes,
+    },
+    position::{ChunkPosition, Position},
+};
+
+use super::{
+    generator::required_block,
+    world_info::{WorldSeed, name_salt},
+};
+
+/// The size of the grid patches are scattered on, in blocks.
+const CELL_SIZE: i32 = 64;
+/// How many patches a cell has on average, with a frequency of 1.
+const PATCHES_PER_CELL: f32 = 0.3;
+/// The radius of a patch with a size of 1.
+const PATCH_RADIUS: f32 = 5.;
+/// Patches never grow beyond this, so that a chunk only has to look at the cells around it.
+const MAX_PATCH_RADIUS: f32 = CELL_SIZE as f32 / 2.;
+/// How much of a patch is ore with a richness of 1. The rest is left as stone.
+const PATCH_DENSITY: f32 = 0.6;
+/// `distance_scaling` is per this many blocks from spawn.
+const DISTANCE_SCALING_UNIT: f32 = 1000.;
+/// How much the edges of patches are roughened by noise. 0 gives perfect spheres.
+const EDGE_ROUGHNESS: f32 = 0.35;
+const EDGE_FREQUENCY: f32 = 0.15;
+
+/// A resource with its block looked up.
+pub struct PlacedResource {
+    pub prototype: &'static ResourcePrototype,
+    pub block: &'static BlockPrototype,
+    salt: u64,
+}
+
+/// A single patch of ore.
+#[derive(Clone, Copy, Debug)]
+pub struct Patch {
+    pub block: &'static BlockPrototype,
+    pub center: Vec3,
+    pub radius: f32,
+    /// the chance of each block inside the patch being ore
+    pub density: f32,
+    salt: u64,
+}
+
+impl Patch {
+    /// How far from the center rough edges can reach.
+    #[must_use]
+    pub const fn reach(&self) -> f32 {
+        self.radius * (1. + EDGE_ROUGHNESS)
+    }
+}
+
+pub struct ResourcePlacer {
+    resources: Vec<PlacedResource>,
+    seed: WorldSeed,
+}
+
+impl ResourcePlacer {
+    /// # Errors
+    /// If a resource's block doesn't exist.
+    pub fn new(
+        resource_prototypes: &ResourcePrototypes,
+        block_prototypes: &BlockPrototypes,
+        seed: WorldSeed,
+    ) -> Result<Self> {
+        let resources = resource_prototypes
+            .iter()
+            .map(|(_, &prototype)| {
+                Ok(PlacedResource {
+                    prototype,
+                    block: required_block(block_prototypes, &prototype.block)?,
+                    salt: name_salt(&prototype.name),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { resources, seed })
+    }
+
+    /// Every patch that reaches into the given chunk.
+    #[must_use]
+    pub fn patches_in_chunk(&self, chunk_position: ChunkPosition) -> ChunkPatches {
+        let min = Position::from(chunk_position).0;
+        self.patches_in(min, min + IVec3::splat(CHUNK_SIZE_I32 - 1))
+    }
+
+    /// Every patch that reaches into the box between `min` and `max`, inclusive.
+    #[must_use]
+    pub fn patches_in(&self, min: IVec3, max: IVec3) -> ChunkPatches {
+        let margin = (MAX_PATCH_RADIUS * (1. + EDGE_ROUGHNESS)).ceil() as i32;
+        let min_cell = (min - margin).div_euclid(IVec3::splat(CELL_SIZE));
+        let max_cell = (max + margin).div_euclid(IVec3::splat(CELL_SIZE));
+        let (min_f32, max_f32) = (min.as_vec3(), max.as_vec3());
+
+        let mut patches = vec![];
+        for resource in &self.resources {
+            for z in min_cell.z..=max_cell.z {
+                for y in min_cell.y..=max_cell.y {
+                    for x in min_cell.x..=max_cell.x {
+                        let cell = IVec3::new(x, y, z);
+                        patches.extend(self.patches_in_cell(resource, cell).filter(|patch| {
+                            let closest = patch.center.clamp(min_f32, max_f32);
+                            closest.distance(patch.center) <= patch.reach()
+                        }));
+                    }
+                }
+            }
+        }
+
+        let mut edge_noise = FastNoise::seeded(self.seed.derive(name_salt("autoplace-edges")));
+        edge_noise.set_frequency(EDGE_FREQUENCY);
+        ChunkPatches {
+            patches,
+            edge_noise,
+            seed: self.seed,
+        }
+    }
+
+    /// The patches whose centers are in this cell. Only depends on the seed and the cell.
+    fn patches_in_cell(
+        &self,
+        resource: &PlacedResource,
+        cell: IVec3,
+    ) -> impl Iterator<Item = Patch> {
+        let settings: &'static AutoplaceSettings = &resource.prototype.autoplace;
+        let block = resource.block;
+        let cell_min = cell * CELL_SIZE;
+        let cell_max = cell_min + IVec3::splat(CELL_SIZE - 1);
+        let overlaps_heights =
+            cell_min.y <= settings.max_height && cell_max.y >= settings.min_height;
+
+        let mut rng = self.seed.cell_rng(cell, resource.salt);
+        let expected = settings.frequency * PATCHES_PER_CELL;
+        let mut count = expected.floor() as usize;
+        if rng.random::<f32>() < expected.fract() {
+            count += 1;
+        }
+        if !overlaps_heights {
+            count = 0;
+        }
+
+        (0..count).filter_map(move |_| {
+            let x = rng.random_range(cell_min.x..=cell_max.x);
+            let z = rng.random_range(cell_min.z..=cell_max.z);
+            // only the part of the cell inside the height range
+            let y = rng.random_range(
+                cell_min.y.max(settings.min_height)..=cell_max.y.min(settings.max_height),
+            );
+            let center = IVec3::new(x, y, z).as_vec3();
+
+            let distance = center.xz().length();
+            let scaling = 1. + settings.distance_scaling * distance / DISTANCE_SCALING_UNIT;
+            let radius =
+                (PATCH_RADIUS * settings.size * scaling.sqrt() * rng.random_range(0.7..1.3))
+                    .min(MAX_PATCH_RADIUS);
+            let density = (PATCH_DENSITY * settings.richness * scaling).min(1.);
+            if radius < 1. || density <= 0. {
+                return None;
+            }
+
+            Some(Patch {
+                block,
+                center,
+                radius,
+                density,
+                salt: rng.random(),
+            })
+        })
+    }
+}
+
+/// The patches near one chunk.
+pub struct ChunkPatches {
+    patches: Vec<Patch>,
+    edge_noise: FastNoise,
+    seed: WorldSeed,
+}
+
+impl ChunkPatches {
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.patches.is_empty()
+    }
+
+    /// The ore at a world position, if any. Where patches overlap the first one wins.
+    #[must_use]
+    pub fn ore_at(&self, position: IVec3) -> Option<&'static BlockPrototype> {
+        let point = position.as_vec3();
+        self.patches.iter().find_map(|patch| {
+            let distance = point.distance(patch.center) / patch.radius;
+            if distance > 1. + EDGE_ROUGHNESS {
+                return None;
+            }
+            let edge = self
+                .edge_noise
+                .get_noise3d(point.x, point.y, point.z)
+                .mul_add(EDGE_ROUGHNESS, 1.);
+            if distance > edge {
+                return None;
+            }
+            // thins out towards the edge
+            let chance = patch.density * (1. - distance / edge).sqrt();
+            let roll =
+                (self.seed.hash_position(position, patch.salt) >> 40) as f32 / (1u64 << 24) as f32;
+            (roll < chance).then_some(patch.block)
+        })
+    }
+}
+
+#[test]
+fn patches_match_across_chunk_borders() {
+    use crate::mod_manager::prototypes::{
+        BlockPrototypesBuilder, PrototypesBuilder, RawBlockPrototype, RawResourcePrototype,
+        ResourcePrototypesBuilder,
+    };
+
+    let mut blocks = BlockPrototypesBuilder::new();
+    blocks.add(RawBlockPrototype {
+        name: "ore".into(),
+        is_transparent: false,
+        is_meshable: true,
+        color: Color::BLACK,
+    });
+    let blocks = blocks.build();
+    let mut resources = ResourcePrototypesBuilder::new();
+    resources.add(RawResourcePrototype {
+        name: "ore".into(),
+        block: "ore".into(),
+        // plenty of patches, so that the borders below are likely to cross some
+        autoplace: AutoplaceSettings {
+            frequency: 10.,
+            ..default()
+        },
+    });
+    let resources = resources.build();
+    let placer = ResourcePlacer::new(&resources, &blocks, WorldSeed(99)).expect("Missing blocks");
+
+    for chunk_position in [
+        ChunkPosition::new(0, 0, 0),
+        ChunkPosition::new(-1, -2, 3),
+        ChunkPosition::new(5, 1, -4),
+    ] {
+        let chunk_patches = placer.patches_in_chunk(chunk_position);
+        let min = Position::from(chunk_position).0;
+        for i in 0..CHUNK_SIZE_I32 * CHUNK_SIZE_I32 {
+            // the two faces the chunk shares with its neighbours in -x and +x
+            for x in [0, CHUNK_SIZE_I32 - 1] {
+                let position = min + IVec3::new(x, i % CHUNK_SIZE_I32, i / CHUNK_SIZE_I32);
+                let alone = placer.patches_in(position, position);
+                assert_eq!(
+                    chunk_patches.ore_at(position).map(|block| block.id),
+                    alone.ore_at(position).map(|block| block.id),
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,183 @@
+//! Picks a biome for every column from temperature and humidity noise,
+//! and blends the terrain shape of neighbouring biomes so that there are no walls where they meet.
+
+use anyhow::{Result, bail};
+use bracket_noise::prelude::*;
+
+use crate::mod_manager::prototypes::{
+    BiomePrototype, BiomePrototypes, BlockPrototype, BlockPrototypes, Prototypes,
+};
+
+use super::{generator::required_block, world_info::WorldSeed};
+
+/// How large biomes are. Lower is larger.
+const CLIMATE_FREQUENCY: f32 = 0.0015;
+/// Simplex noise rarely reaches ±1, this stretches it so that biomes at the edges of climate space still appear.
+const CLIMATE_SCALE: f32 = 1.6;
+/// How far apart in climate space two biomes can be and still blend. Larger gives wider transitions.
+const BLEND_WIDTH: f32 = 0.25;
+
+const TEMPERATURE_SALT: u64 = 0x7e49;
+const HUMIDITY_SALT: u64 = 0x4b1d;
+
+/// A biome with its blocks looked up.
+#[derive(Clone, Copy)]
+pub struct Biome {
+    pub prototype: &'static BiomePrototype,
+    pub surface: &'static BlockPrototype,
+    pub subsurface: &'static BlockPrototype,
+    pub filler: &'static BlockPrototype,
+}
+
+impl Biome {
+    /// # Errors
+    /// If one of the biome's blocks doesn't exist.
+    pub fn new(
+        prototype: &'static BiomePrototype,
+        block_prototypes: &BlockPrototypes,
+    ) -> Result<Self> {
+        Ok(Self {
+            prototype,
+            surface: required_block(block_prototypes, &prototype.surface_block)?,
+            subsurface: required_block(block_prototypes, &prototype.subsurface_block)?,
+            filler: required_block(block_prototypes, &prototype.filler_block)?,
+        })
+    }
+
+    /// The block `depth` blocks below the surface of this biome.
+    #[must_use]
+    pub const fn block_at_depth(&self, depth: f32) -> &'static BlockPrototype {
+        if depth < 1. {
+            self.surface
+        } else if depth < 1. + self.prototype.subsurface_depth as f32 {
+            self.subsurface
+        } else {
+            self.filler
+        }
+    }
+}
+
+/// The shape of the terrain at a single column, blended from every biome near it in climate space.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TerrainShape {
+    pub height: f32,
+    pub height_variation: f32,
+    pub overhang: f32,
+}
+
+/// The biome of a column, and its blended shape.
+#[derive(Clone, Copy)]
+pub struct BiomeSample {
+    /// the biome closest to the column's climate. Decides the blocks.
+    pub biome: Biome,
+    pub shape: TerrainShape,
+}
+
+pub struct BiomeMap {
+    biomes: Vec<Biome>,
+    seed: WorldSeed,
+}
+
+impl BiomeMap {
+    /// # Errors
+    /// If there are no biomes, or a biome uses a block that doesn't exist.
+    pub fn new(
+        biome_prototypes: &BiomePrototypes,
+        block_prototypes: &BlockPrototypes,
+        seed: WorldSeed,
+    ) -> Result<Self> {
+        let biomes = biome_prototypes
+            .iter()
+            .map(|(_, &prototype)| Biome::new(prototype, block_prototypes))
+            .collect::<Result<Vec<_>>>()?;
+        if biomes.is_empty() {
+            bail!("World generation needs at least one biome prototype.");
+        }
+        Ok(Self { biomes, seed })
+    }
+
+    pub fn biomes(&self) -> impl Iterator<Item = &Biome> {
+        self.biomes.iter()
+    }
+
+    /// No terrain is ever above this height.
+    #[must_use]
+    pub fn max_height(&self) -> f32 {
+        self.biomes
+            .iter()
+            .map(|biome| biome.prototype.height + biome.prototype.height_variation)
+            .fold(f32::MIN, f32::max)
+    }
+
+    /// Samples biomes. Setting up the noise isn't free, so create one sampler per chunk rather than per column.
+    #[must_use]
+    pub fn sampler(&self) -> BiomeSampler<'_> {
+        let climate_noise = |salt| {
+            let mut noise = FastNoise::seeded(self.seed.derive(salt));
+            noise.set_noise_type(NoiseType::Simplex);
+            noise.set_frequency(CLIMATE_FREQUENCY);
+            noise
+        };
+        BiomeSampler {
+            map: self,
+            temperature: climate_noise(TEMPERATURE_SALT),
+            humidity: climate_noise(HUMIDITY_SALT),
+        }
+    }
+}
+
+pub struct BiomeSampler<'a> {
+    map: &'a BiomeMap,
+    temperature: FastNoise,
+    humidity: FastNoise,
+}
+
+impl BiomeSampler<'_> {
+    /// The climate of a column, each between -1 and 1.
+    #[must_use]
+    pub fn climate(&self, x: f32, z: f32) -> (f32, f32) {
+        let temperature = (self.temperature.get_noise(x, z) * CLIMATE_SCALE).clamp(-1., 1.);
+        let humidity = (self.humidity.get_noise(x, z) * CLIMATE_SCALE).clamp(-1., 1.);
+        (temperature, humidity)
+    }
+
+    #[must_use]
+    pub fn sample(&self, x: f32, z: f32) -> BiomeSample {
+        let (temperature, humidity) = self.climate(x, z);
+        let distances_squared = self.map.biomes.iter().map(|biome| {
+            let dt = biome.prototype.temperature - temperature;
+            let dh = biome.prototype.humidity - humidity;
+            dt * dt + dh * dh
+        });
+
+        let (closest, closest_distance_squared) = distances_squared
+            .clone()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .expect("BiomeMap always has at least one biome");
+
+        // weights are relative to the closest biome so that they never all round to zero
+        let mut shape = TerrainShape::default();
+        let mut total_weight = 0.;
+        for (biome, distance_squared) in self.map.biomes.iter().zip(distances_squared) {
+            let weight = (-(distance_squared - closest_distance_squared)
+                / (BLEND_WIDTH * BLEND_WIDTH))
+                .exp();
+            shape.height = biome.prototype.height.mul_add(weight, shape.height);
+            shape.height_variation = biome
+                .prototype
+                .height_variation
+                .mul_add(weight, shape.height_variation);
+            shape.overhang = biome.prototype.overhang.mul_add(weight, shape.overhang);
+            total_weight += weight;
+        }
+        shape.height /= total_weight;
+        shape.height_variation /= total_weight;
+        shape.overhang /= total_weight;
+
+        BiomeSample {
+            biome: self.map.biomes[closest],
+            shape,
+        }
+    }
+}
@@ -0,0 +1,165 @@
+//! Carves caves out of solid terrain.
+//! Long tunnels form where two noise fields are both close to zero ("spaghetti"),
+//! and large caverns where a third is high ("cheese").
+//! The noise is only sampled on a coarse lattice and interpolated in between, which makes it cheap,
+//! and lets whole chunks be recognised as completely carved or completely solid without looking at every block.
+
+use bevy::prelude::*;
+use bracket_noise::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    chunky::chunk::{CHUNK_SIZE, CHUNK_SIZE_I32},
+    position::{ChunkPosition, Position},
+};
+
+use super::world_info::WorldSeed;
+
+/// Chosen when the world is created and saved with it.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CaveSettings {
+    /// 0 disables caves, 1 is normal, larger values carve more.
+    pub density: f32,
+    /// caves are only carved between these heights, in blocks
+    pub min_height: i32,
+    pub max_height: i32,
+}
+
+impl Default for CaveSettings {
+    fn default() -> Self {
+        Self {
+            density: 1.,
+            min_height: -160,
+            max_height: 190,
+        }
+    }
+}
+
+/// Blocks between lattice points. Must divide `CHUNK_SIZE`.
+const LATTICE_SPACING: usize = 4;
+const LATTICE_SIZE: usize = CHUNK_SIZE / LATTICE_SPACING + 1;
+
+const SPAGHETTI_FREQUENCY: f32 = 0.018;
+/// How wide tunnels are with a density of 1.
+const SPAGHETTI_WIDTH: f32 = 0.07;
+const CHEESE_FREQUENCY: f32 = 0.011;
+/// How much of the rock is caverns with a density of 1. Fractions of the noise range, not of the volume.
+const CHEESE_AMOUNT: f32 = 0.3;
+/// Caves shrink to nothing over this many blocks at the edges of the height range.
+const HEIGHT_FADE: f32 = 16.;
+
+const SPAGHETTI_A_SALT: u64 = 0xca1e_0001;
+const SPAGHETTI_B_SALT: u64 = 0xca1e_0002;
+const CHEESE_SALT: u64 = 0xca1e_0003;
+
+pub struct CaveCarver {
+    settings: CaveSettings,
+    seed: WorldSeed,
+}
+
+impl CaveCarver {
+    #[must_use]
+    pub const fn new(settings: CaveSettings, seed: WorldSeed) -> Self {
+        Self { settings, seed }
+    }
+
+    /// Samples the caves of a single chunk.
+    #[must_use]
+    pub fn carve_chunk(&self, chunk_position: ChunkPosition) -> ChunkCaves {
+        let min = Position::from(chunk_position).0;
+        let max = min + IVec3::splat(CHUNK_SIZE_I32);
+        if self.settings.density <= 0.
+            || max.y < self.settings.min_height
+            || min.y > self.settings.max_height
+        {
+            return ChunkCaves::Solid;
+        }
+
+        let noise = |salt, frequency| {
+            let mut noise = FastNoise::seeded(self.seed.derive(salt));
+            noise.set_noise_type(NoiseType::Simplex);
+            noise.set_frequency(frequency);
+            noise
+        };
+        let spaghetti_a = noise(SPAGHETTI_A_SALT, SPAGHETTI_FREQUENCY);
+        let spaghetti_b = noise(SPAGHETTI_B_SALT, SPAGHETTI_FREQUENCY);
+        let cheese = noise(CHEESE_SALT, CHEESE_FREQUENCY);
+
+        // lattice points sit on multiples of the spacing in world space, so neighbouring chunks agree on their shared face
+        let mut lattice = Box::new([[[0.; LATTICE_SIZE]; LATTICE_SIZE]; LATTICE_SIZE]);
+        for (x, plane) in lattice.iter_mut().enumerate() {
+            for (y, row) in plane.iter_mut().enumerate() {
+                for (z, value) in row.iter_mut().enumerate() {
+                    let point = (min
+                        + IVec3::new(x as i32, y as i32, z as i32) * LATTICE_SPACING as i32)
+                        .as_vec3();
+                    let density = self.settings.density * self.height_fade(point.y);
+
+                    let tunnel_distance = spaghetti_a
+                        .get_noise3d(point.x, point.y, point.z)
+                        .abs()
+                        .max(spaghetti_b.get_noise3d(point.x, point.y, point.z).abs());
+                    let tunnel = SPAGHETTI_WIDTH.mul_add(density, -tunnel_distance);
+                    let cavern = cheese.get_noise3d(point.x, point.y, point.z)
+                        - CHEESE_AMOUNT.mul_add(-density, 1.);
+                    *value = tunnel.max(cavern);
+                }
+            }
+        }
+
+        let corners = lattice.iter().flatten().flatten();
+        if corners.clone().all(|&value| value > 0.) {
+            ChunkCaves::Carved
+        } else if corners.clone().all(|&value| value <= 0.) {
+            ChunkCaves::Solid
+        } else {
+            ChunkCaves::Partial(lattice)
+        }
+    }
+
+    /// 1 inside the height range, falling to 0 at its edges.
+    fn height_fade(&self, y: f32) -> f32 {
+        let below = (y - self.settings.min_height as f32) / HEIGHT_FADE;
+        let above = (self.settings.max_height as f32 - y) / HEIGHT_FADE;
+        below.min(above).clamp(0., 1.)
+    }
+}
+
+/// The caves in one chunk.
+/// A block is carved where the interpolated lattice value is above 0. Interpolation never leaves the range
+/// of the surrounding lattice points, so if every point agrees, so does every block in the chunk.
+pub enum ChunkCaves {
+    /// no block is carved
+    Solid,
+    /// every block is carved
+    Carved,
+    Partial(Box<[[[f32; LATTICE_SIZE]; LATTICE_SIZE]; LATTICE_SIZE]>),
+}
+
+impl ChunkCaves {
+    /// True if the block at this position within the chunk is carved out.
+    #[must_use]
+    pub fn is_carved(&self, local: Position) -> bool {
+        let lattice = match self {
+            Self::Solid => return false,
+            Self::Carved => return true,
+            Self::Partial(lattice) => lattice,
+        };
+
+        let cell = local.0.as_uvec3() / LATTICE_SPACING as u32;
+        let t = (local.0 - (cell * LATTICE_SPACING as u32).as_ivec3()).as_vec3()
+            / LATTICE_SPACING as f32;
+        let (x, y, z) = (cell.x as usize, cell.y as usize, cell.z as usize);
+        let at = |dx: usize, dy: usize, dz: usize| lattice[x + dx][y + dy][z + dz];
+
+        let lerp = |a: f32, b: f32, t: f32| (b - a).mul_add(t, a);
+        let x00 = lerp(at(0, 0, 0), at(1, 0, 0), t.x);
+        let x10 = lerp(at(0, 1, 0), at(1, 1, 0), t.x);
+        let x01 = lerp(at(0, 0, 1), at(1, 0, 1), t.x);
+        let x11 = lerp(at(0, 1, 1), at(1, 1, 1), t.x);
+        let y0 = lerp(x00, x10, t.y);
+        let y1 = lerp(x01, x11, t.y);
+        lerp(y0, y1, t.z) > 0.
+    }
+}
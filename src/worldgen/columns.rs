@@ -0,0 +1,151 @@
+//! Column data shared by every chunk in a column of chunks: the biome and terrain shape of each block column.
+//! Computed once per column and cached, instead of once for every chunk stacked on top of each other.
+
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use bevy::{platform::collections::HashMap, prelude::*};
+
+use crate::{
+    chunky::chunk::{CHUNK_SIZE, CHUNK_SIZE_I32, CHUNK_SIZE2},
+    mod_manager::prototypes::BlockPrototype,
+};
+
+use super::biome::{Biome, TerrainShape};
+
+/// How many chunk columns are kept. Worldgen works outwards from the player,
+/// so recently used columns are the ones most likely to be needed again.
+pub const COLUMN_CACHE_SIZE: usize = 512;
+
+/// A single block column.
+#[derive(Clone, Copy)]
+pub struct ColumnSample {
+    pub biome: Biome,
+    pub shape: TerrainShape,
+}
+
+impl ColumnSample {
+    /// The highest the surface can be in this column.
+    #[must_use]
+    pub const fn max_surface(&self) -> f32 {
+        self.shape.height + self.shape.height_variation
+    }
+
+    /// The lowest the surface can be in this column.
+    #[must_use]
+    pub const fn min_surface(&self) -> f32 {
+        self.shape.height - self.shape.height_variation
+    }
+}
+
+/// Every block column of a column of chunks.
+pub struct ChunkColumn {
+    samples: Box<[ColumnSample]>,
+    max_surface: f32,
+    // deeper than this, every block is `filler`
+    filler_below: f32,
+    filler: Option<&'static BlockPrototype>,
+}
+
+impl ChunkColumn {
+    /// Builds a column by asking for every block column, given its local x and z.
+    pub fn from_fn(mut sample_at: impl FnMut(i32, i32) -> ColumnSample) -> Self {
+        let samples: Box<[ColumnSample]> = (0..CHUNK_SIZE2)
+            .map(|i| sample_at((i % CHUNK_SIZE) as i32, (i / CHUNK_SIZE) as i32))
+            .collect();
+
+        let max_surface = samples
+            .iter()
+            .map(ColumnSample::max_surface)
+            .fold(f32::MIN, f32::max);
+        let filler_below = samples
+            .iter()
+            .map(|sample| {
+                sample.min_surface() - 1. - sample.biome.prototype.subsurface_depth as f32
+            })
+            .fold(f32::MAX, f32::min);
+        let filler = samples[0].biome.filler;
+        let filler = samples
+            .iter()
+            .all(|sample| sample.biome.filler == filler)
+            .then_some(filler);
+
+        Self {
+            samples,
+            max_surface,
+            filler_below,
+            filler,
+        }
+    }
+
+    /// # Panics
+    /// If x or z are outside the chunk.
+    #[must_use]
+    pub fn get(&self, x: i32, z: i32) -> &ColumnSample {
+        assert!(
+            (0..CHUNK_SIZE_I32).contains(&x) && (0..CHUNK_SIZE_I32).contains(&z),
+            "Expected x and z to be local to the chunk column."
+        );
+        &self.samples[x as usize + z as usize * CHUNK_SIZE]
+    }
+
+    /// No block above this height is solid.
+    #[must_use]
+    pub const fn max_surface(&self) -> f32 {
+        self.max_surface
+    }
+
+    /// The block every column is filled with below `height`, if there is one.
+    /// Doesn't account for caves or ores.
+    #[must_use]
+    pub fn filler_below(&self, height: f32) -> Option<&'static BlockPrototype> {
+        self.filler.filter(|_| height < self.filler_below)
+    }
+}
+
+/// Shared by every worldgen thread.
+#[derive(Default)]
+pub struct ColumnCache {
+    inner: Mutex<ColumnCacheInner>,
+}
+
+#[derive(Default)]
+struct ColumnCacheInner {
+    columns: HashMap<IVec2, Arc<ChunkColumn>>,
+    // oldest first
+    order: VecDeque<IVec2>,
+}
+
+impl ColumnCache {
+    /// Returns the cached column at this chunk x and z, or builds it with `build`.
+    /// The lock isn't held while building, so two threads may build the same column. The first one is kept.
+    pub fn get_or_insert_with(
+        &self,
+        column_position: IVec2,
+        build: impl FnOnce() -> ChunkColumn,
+    ) -> Arc<ChunkColumn> {
+        if let Some(column) = self.lock().columns.get(&column_position) {
+            return column.clone();
+        }
+
+        let column = Arc::new(build());
+        let mut inner = self.lock();
+        if let Some(existing) = inner.columns.get(&column_position) {
+            return existing.clone();
+        }
+        if inner.order.len() >= COLUMN_CACHE_SIZE {
+            if let Some(oldest) = inner.order.pop_front() {
+                inner.columns.remove(&oldest);
+            }
+        }
+        inner.columns.insert(column_position, column.clone());
+        inner.order.push_back(column_position);
+        column
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, ColumnCacheInner> {
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
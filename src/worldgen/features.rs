@@ -0,0 +1,305 @@
+//! Places features such as trees and boulders, which may cross chunk borders.
+//!
+//! Features stand on the surface as seen from the sky. A feature belongs to the chunk its ground block is in,
+//! and where a chunk's features go only depends on the seed, the surface height and that chunk's own terrain.
+//! To decorate a chunk, the placements of it and its 26 neighbours are worked out again from their terrain,
+//! and only the blocks that land inside the chunk are written. Every chunk a feature touches
+//! sees the same placement, so the feature is whole no matter which chunk is decorated first.
+
+use anyhow::{Result, anyhow};
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::{
+    chunky::{
+        chunk::{CHUNK_SIZE_I32, ChunkData},
+        chunks_refs::ChunkRefs,
+    },
+    mod_manager::prototypes::{
+        BiomePrototype, BiomePrototypes, BlockPrototype, BlockPrototypes, FeaturePrototype,
+        FeaturePrototypes, Prototypes,
+    },
+    position::Position,
+};
+
+use super::{
+    biome::BiomeSampler,
+    generator::required_block,
+    world_info::{WorldSeed, name_salt},
+};
+
+/// A feature with its blocks and biomes looked up.
+pub struct Feature {
+    pub prototype: &'static FeaturePrototype,
+    /// empty means every biome
+    biomes: Vec<&'static BiomePrototype>,
+    /// empty means any solid block
+    place_on: Vec<&'static BlockPrototype>,
+    blocks: Vec<(IVec3, &'static BlockPrototype)>,
+    salt: u64,
+}
+
+impl Feature {
+    /// # Errors
+    /// If the feature uses a block or biome that doesn't exist.
+    pub fn new(
+        prototype: &'static FeaturePrototype,
+        block_prototypes: &BlockPrototypes,
+        biome_prototypes: &BiomePrototypes,
+    ) -> Result<Self> {
+        let biomes = prototype
+            .biomes
+            .iter()
+            .map(|name| {
+                biome_prototypes.get(name).ok_or_else(|| {
+                    anyhow!("Feature {} uses unknown biome `{name}`.", prototype.name)
+                })
+            })
+            .collect::<Result<_>>()?;
+        let place_on = prototype
+            .place_on
+            .iter()
+            .map(|name| required_block(block_prototypes, name))
+            .collect::<Result<_>>()?;
+        let blocks = prototype
+            .blocks
+            .iter()
+            .map(|(offset, name)| Ok((*offset, required_block(block_prototypes, name)?)))
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            prototype,
+            biomes,
+            place_on,
+            blocks,
+            salt: name_salt(&prototype.name),
+        })
+    }
+}
+
+pub struct FeaturePlacer {
+    features: Vec<Feature>,
+    seed: WorldSeed,
+}
+
+impl FeaturePlacer {
+    /// # Errors
+    /// If a feature uses a block or biome that doesn't exist.
+    pub fn new(
+        feature_prototypes: &FeaturePrototypes,
+        block_prototypes: &BlockPrototypes,
+        biome_prototypes: &BiomePrototypes,
+        seed: WorldSeed,
+    ) -> Result<Self> {
+        let features = feature_prototypes
+            .iter()
+            .map(|(_, &prototype)| Feature::new(prototype, block_prototypes, biome_prototypes))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { features, seed })
+    }
+
+    /// Writes every feature that reaches into the center chunk of `terrain`.
+    /// `surface_height` is the y of the highest solid block at world x and z, ignoring caves.
+    /// Returns `None` if no block changed.
+    #[must_use]
+    pub fn decorate(
+        &self,
+        terrain: &ChunkRefs,
+        biomes: &BiomeSampler,
+        surface_height: &dyn Fn(i32, i32) -> Option<i32>,
+    ) -> Option<ChunkData> {
+        if self.features.is_empty() {
+            return None;
+        }
+        let center_min = Position::from(terrain.center_chunk_position).0;
+        let mut decorated: Option<ChunkData> = None;
+
+        // the neighbours are in the same order around every chunk, so where features overlap
+        // the same one wins on both sides of a border
+        for source in &terrain.adjacent_chunks {
+            for feature in &self.features {
+                for origin in self.placements(feature, source, biomes, surface_height) {
+                    for &(block_offset, block) in &feature.blocks {
+                        let local = origin + block_offset - center_min;
+                        if local.cmplt(IVec3::ZERO).any()
+                            || local.cmpge(IVec3::splat(CHUNK_SIZE_I32)).any()
+                        {
+                            continue;
+                        }
+                        let index = Position(local).into();
+                        // features only fill air, they never cut into terrain or other features
+                        let current = decorated.as_ref().unwrap_or(&*terrain.adjacent_chunks[13]);
+                        if current.get_block(index).is_meshable {
+                            continue;
+                        }
+                        decorated
+                            .get_or_insert_with(|| ChunkData::clone(&terrain.adjacent_chunks[13]))
+                            .set_block(index, block);
+                    }
+                }
+            }
+        }
+        decorated
+    }
+
+    /// The world positions a feature is placed at from this chunk.
+    /// Only depends on the seed, the surface height and the chunk's terrain.
+    fn placements(
+        &self,
+        feature: &Feature,
+        source: &ChunkData,
+        biomes: &BiomeSampler,
+        surface_height: &dyn Fn(i32, i32) -> Option<i32>,
+    ) -> Vec<IVec3> {
+        let frequency = feature.prototype.frequency;
+        let mut rng = self.seed.chunk_rng(source.position, feature.salt);
+        let mut attempts = frequency.floor() as usize;
+        if rng.random::<f32>() < frequency.fract() {
+            attempts += 1;
+        }
+
+        let source_min = Position::from(source.position).0;
+        let mut origins = vec![];
+        for _ in 0..attempts {
+            let x = rng.random_range(0..CHUNK_SIZE_I32);
+            let z = rng.random_range(0..CHUNK_SIZE_I32);
+            // the surface may be in the top layer of this chunk, with the air above it in the next one.
+            // only the chunk holding the ground block places the feature.
+            let Some(surface) = surface_height(source_min.x + x, source_min.z + z) else {
+                continue;
+            };
+            let y = surface - source_min.y;
+            if !(0..CHUNK_SIZE_I32).contains(&y) {
+                continue;
+            }
+
+            // caves may have carved the ground away. they never fill the air above it.
+            let ground = source.get_block(Position::new(x, y, z).into());
+            if !ground.is_meshable {
+                continue;
+            }
+            if !feature.place_on.is_empty() && !feature.place_on.contains(&ground) {
+                continue;
+            }
+            let origin = source_min + IVec3::new(x, y + 1, z);
+            if !feature.biomes.is_empty() {
+                let biome = biomes.sample(origin.x as f32, origin.z as f32).biome;
+                if !feature.biomes.contains(&biome.prototype) {
+                    continue;
+                }
+            }
+            origins.push(origin);
+        }
+        origins
+    }
+}
+
+#[test]
+fn features_are_whole_across_chunk_borders() {
+    use std::sync::Arc;
+
+    use bevy::platform::collections::HashMap;
+
+    use crate::{
+        chunky::chunk::set_block_registry,
+        mod_manager::prototypes::{
+            BiomePrototypesBuilder, BlockPrototypesBuilder, FeaturePrototypesBuilder,
+            PrototypesBuilder, RawBiomePrototype, RawBlockPrototype, RawFeaturePrototype,
+        },
+        position::ChunkPosition,
+        worldgen::biome::BiomeMap,
+    };
+
+    let mut blocks = BlockPrototypesBuilder::new();
+    for (name, is_meshable) in [("air", false), ("stone", true), ("leaves", true)] {
+        blocks.add(RawBlockPrototype {
+            name: name.into(),
+            is_transparent: !is_meshable,
+            is_meshable,
+            color: Color::BLACK,
+        });
+    }
+    let blocks = blocks.build();
+    // decoration reads blocks through the global registry. No other test sets it.
+    set_block_registry(&blocks);
+    let mut biomes = BiomePrototypesBuilder::new();
+    biomes.add(RawBiomePrototype {
+        name: "plains".into(),
+        temperature: 0.,
+        humidity: 0.,
+        surface_block: "stone".into(),
+        subsurface_block: "stone".into(),
+        filler_block: "stone".into(),
+        subsurface_depth: 3,
+        height: 0.,
+        height_variation: 0.,
+        overhang: 0.,
+    });
+    let biomes = biomes.build();
+    // a flat bush, wide enough to cross the border of the chunk it stands in
+    let mut features = FeaturePrototypesBuilder::new();
+    features.add(RawFeaturePrototype {
+        name: "bush".into(),
+        frequency: 16.,
+        biomes: vec![],
+        place_on: vec!["stone".into()],
+        blocks: (-2..=2)
+            .flat_map(|x| (-2..=2).map(move |z| (IVec3::new(x, 0, z), "leaves".into())))
+            .collect(),
+    });
+    let features = features.build();
+    let placer_with_seed = |seed| {
+        FeaturePlacer::new(&features, &blocks, &biomes, WorldSeed(seed)).expect("Missing blocks")
+    };
+    let placer = placer_with_seed(7);
+    let biome_map = BiomeMap::new(&biomes, &blocks, WorldSeed(7)).expect("Missing blocks");
+    let biome_sampler = biome_map.sampler();
+
+    // the ground is the top layer of the chunks at y 0, so every bush is in the chunks above them
+    let block = |name| blocks.get(name).expect("The block was added above");
+    let surface_height = |_: i32, _: i32| Some(CHUNK_SIZE_I32 - 1);
+    let mut terrain = HashMap::new();
+    for x in -2..=2 {
+        for z in -2..=2 {
+            for (y, name) in [(0, "stone"), (1, "air"), (2, "air")] {
+                let position = ChunkPosition::new(x, y, z);
+                let chunk = ChunkData::homogeneous(position, block(name));
+                terrain.insert(position, Arc::new(chunk));
+            }
+        }
+    }
+
+    let mut decorated = HashMap::new();
+    for x in -1..=1 {
+        for z in -1..=1 {
+            let position = ChunkPosition::new(x, 1, z);
+            let refs = ChunkRefs::try_from_map(&terrain, position).expect("Terrain is loaded");
+            let chunk = placer.decorate(&refs, &biome_sampler, &surface_height);
+            // another placer with the same seed decorates the chunk the same way
+            let again = placer_with_seed(7).decorate(&refs, &biome_sampler, &surface_height);
+            assert_eq!(chunk, again);
+            decorated.insert(position, chunk.expect("Every chunk has bushes of its own"));
+        }
+    }
+
+    // every block of every bush is there, whichever chunk it landed in
+    let mut crossed = 0;
+    for x in -2..=2 {
+        for z in -2..=2 {
+            let source = &terrain[&ChunkPosition::new(x, 0, z)];
+            for feature in &placer.features {
+                for origin in placer.placements(feature, source, &biome_sampler, &surface_height) {
+                    for &(offset, _) in &feature.blocks {
+                        let position = Position(origin + offset);
+                        let chunk_position = ChunkPosition::from(position);
+                        if let Some(chunk) = decorated.get(&chunk_position) {
+                            let index = position.local_to_chunk().into();
+                            assert_eq!(chunk.get_block(index), block("leaves"));
+                            crossed += usize::from(chunk_position.0.xz() != IVec2::new(x, z));
+                        }
+                    }
+                }
+            }
+        }
+    }
+    assert!(crossed > 0, "No bush crossed a chunk border.");
+}
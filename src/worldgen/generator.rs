@@ -0,0 +1,262 @@
+//! World generators turn a chunk position into its terrain.
+//! The world picks one by name from [`WorldGenerators`] when it is created, see [`WorldSettings`].
+
+use std::{collections::BTreeMap, path::PathBuf, sync::Arc};
+
+use anyhow::{Context, Result, anyhow};
+use bevy::{ecs::system::SystemParam, prelude::*};
+
+use crate::{
+    chunky::{chunk::ChunkData, chunks_refs::ChunkRefs},
+    mod_manager::prototypes::{
+        BiomePrototype, BiomePrototypes, BlockPrototype, BlockPrototypes, FeaturePrototypes,
+        FlatPresetPrototypes, Prototypes, ResourcePrototypes,
+    },
+    position::ChunkPosition,
+};
+
+use super::{
+    caves::CaveSettings,
+    heightmap_generator::{HeightmapSettings, HeightmapWorldGenerator},
+    noise_generator::NoiseWorldGenerator,
+    presets::{DebugWorldGenerator, FlatSettings, FlatWorldGenerator, VoidWorldGenerator},
+    world_info::{WorldInfo, WorldSeed},
+};
+
+pub struct WorldGenPlugin;
+
+impl Plugin for WorldGenPlugin {
+    fn build(&self, app: &mut App) {
+        app.world_mut()
+            .get_resource_or_init::<WorldGenerators>()
+            .register_builtin();
+        app.init_resource::<WorldSettings>().add_systems(
+            Update,
+            create_world_generator.run_if(resource_added::<BlockPrototypes>),
+        );
+    }
+}
+
+/// Generates terrain. Called from worker threads, so it must not depend on the order chunks are generated in.
+pub trait WorldGenerator: Send + Sync + 'static {
+    fn generate(&self, chunk_position: ChunkPosition) -> ChunkData;
+
+    /// Places features that may cross chunk borders into the center chunk of `terrain`.
+    /// `terrain` holds the chunk and its 26 neighbours exactly as `generate` made them.
+    /// Returns `None` if the chunk is unchanged.
+    fn decorate(&self, _terrain: &ChunkRefs) -> Option<ChunkData> {
+        None
+    }
+
+    /// The y of the highest solid block at world x and z, before features are placed.
+    /// Returns `None` if the generator can't tell, or the column has no solid blocks.
+    fn surface_height(&self, _x: i32, _z: i32) -> Option<i32> {
+        None
+    }
+
+    /// The biome at world x and z, for generators that have biomes.
+    fn biome_at(&self, _x: i32, _z: i32) -> Option<&'static BiomePrototype> {
+        None
+    }
+}
+
+/// Everything a generator is built from.
+pub struct GeneratorContext<'a> {
+    pub block_prototypes: &'a BlockPrototypes,
+    pub biome_prototypes: &'a BiomePrototypes,
+    pub resource_prototypes: &'a ResourcePrototypes,
+    pub feature_prototypes: &'a FeaturePrototypes,
+    pub flat_preset_prototypes: &'a FlatPresetPrototypes,
+    pub caves: CaveSettings,
+    /// only set for worlds created with heightmap settings
+    pub heightmap: Option<&'a HeightmapSettings>,
+    /// only set for flat worlds that don't use the default preset
+    pub flat: Option<&'a FlatSettings>,
+    /// All randomness in the generator must come from the seed.
+    pub seed: WorldSeed,
+}
+
+/// Builds a generator once the prototypes are loaded.
+pub type WorldGeneratorFactory = fn(&GeneratorContext) -> Result<Arc<dyn WorldGenerator>>;
+
+/// Every generator a world can be created with, by name.
+#[derive(Resource, Default, Clone)]
+pub struct WorldGenerators(BTreeMap<&'static str, WorldGeneratorFactory>);
+
+impl WorldGenerators {
+    pub fn register(&mut self, name: &'static str, factory: WorldGeneratorFactory) {
+        if self.0.insert(name, factory).is_some() {
+            warn!("World generator {name} registered twice. The last one wins.");
+        }
+    }
+
+    /// # Errors
+    /// If no generator is registered under `name`, or the generator can not be built.
+    pub fn create(
+        &self,
+        name: &str,
+        context: &GeneratorContext,
+    ) -> Result<Arc<dyn WorldGenerator>> {
+        let factory = self
+            .0
+            .get(name)
+            .ok_or_else(|| anyhow!("Unknown world generator `{name}`."))?;
+        factory(context)
+    }
+
+    /// Registers the generators that come with the game.
+    pub fn register_builtin(&mut self) {
+        self.register(NoiseWorldGenerator::NAME, |context| {
+            Ok(Arc::new(NoiseWorldGenerator::new(context)?))
+        });
+        self.register(HeightmapWorldGenerator::NAME, |context| {
+            Ok(Arc::new(HeightmapWorldGenerator::new(context)?))
+        });
+        self.register(FlatWorldGenerator::NAME, |context| {
+            Ok(Arc::new(FlatWorldGenerator::new(context)?))
+        });
+        self.register(VoidWorldGenerator::NAME, |context| {
+            Ok(Arc::new(VoidWorldGenerator::new(context)?))
+        });
+        self.register(DebugWorldGenerator::NAME, |context| {
+            Ok(Arc::new(DebugWorldGenerator::new(context)?))
+        });
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.0.keys().copied()
+    }
+}
+
+pub trait AddWorldGenerator {
+    fn add_world_generator(
+        &mut self,
+        name: &'static str,
+        factory: WorldGeneratorFactory,
+    ) -> &mut Self;
+}
+
+impl AddWorldGenerator for App {
+    fn add_world_generator(
+        &mut self,
+        name: &'static str,
+        factory: WorldGeneratorFactory,
+    ) -> &mut Self {
+        self.world_mut()
+            .get_resource_or_init::<WorldGenerators>()
+            .register(name, factory);
+        self
+    }
+}
+
+/// Insert this before the app runs to pick another world.
+/// Everything but the directory is only used when the world is created, after that it is read from the save.
+#[derive(Resource, Clone)]
+pub struct WorldSettings {
+    pub save_directory: PathBuf,
+    pub generator: String,
+    /// A random seed is picked if this is `None`.
+    pub seed: Option<u64>,
+    pub caves: CaveSettings,
+    /// needed by the heightmap generator
+    pub heightmap: Option<HeightmapSettings>,
+    /// used by the flat generator. The default preset is used if this is `None`.
+    pub flat: Option<FlatSettings>,
+}
+
+impl Default for WorldSettings {
+    fn default() -> Self {
+        Self {
+            save_directory: PathBuf::from("saves/world"),
+            generator: NoiseWorldGenerator::NAME.to_string(),
+            seed: None,
+            caves: CaveSettings::default(),
+            heightmap: None,
+            flat: None,
+        }
+    }
+}
+
+/// The generator of the current world.
+#[derive(Resource, Clone, Deref)]
+pub struct ActiveWorldGenerator(pub Arc<dyn WorldGenerator>);
+
+/// The prototypes generators are built from.
+#[derive(SystemParam)]
+struct GeneratorPrototypes<'w> {
+    blocks: Res<'w, BlockPrototypes>,
+    biomes: Res<'w, BiomePrototypes>,
+    resources: Res<'w, ResourcePrototypes>,
+    features: Res<'w, FeaturePrototypes>,
+    flat_presets: Res<'w, FlatPresetPrototypes>,
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn create_world_generator(
+    mut commands: Commands,
+    settings: Res<WorldSettings>,
+    generators: Res<WorldGenerators>,
+    prototypes: GeneratorPrototypes,
+    mut exit: EventWriter<AppExit>,
+) {
+    match load_world_generator(&settings, &generators, &prototypes) {
+        Ok((seed, generator)) => {
+            commands.insert_resource(seed);
+            commands.insert_resource(ActiveWorldGenerator(generator));
+        }
+        Err(error) => {
+            error!("Failed to create the world: {error:#}");
+            exit.write(AppExit::error());
+        }
+    }
+}
+
+/// Opens the world, or creates it from `settings`, and builds its generator.
+fn load_world_generator(
+    settings: &WorldSettings,
+    generators: &WorldGenerators,
+    prototypes: &GeneratorPrototypes,
+) -> Result<(WorldSeed, Arc<dyn WorldGenerator>)> {
+    let world_info = WorldInfo::load_or_create(&settings.save_directory, || WorldInfo {
+        generator: settings.generator.clone(),
+        seed: WorldSeed(settings.seed.unwrap_or_else(rand::random)),
+        caves: settings.caves,
+        heightmap: settings.heightmap.clone(),
+        flat: settings.flat.clone(),
+    })
+    .context("Failed to open world")?;
+
+    let context = GeneratorContext {
+        block_prototypes: &prototypes.blocks,
+        biome_prototypes: &prototypes.biomes,
+        resource_prototypes: &prototypes.resources,
+        feature_prototypes: &prototypes.features,
+        flat_preset_prototypes: &prototypes.flat_presets,
+        seed: world_info.seed,
+        caves: world_info.caves,
+        heightmap: world_info.heightmap.as_ref(),
+        flat: world_info.flat.as_ref(),
+    };
+    let generator = generators
+        .create(&world_info.generator, &context)
+        .with_context(|| {
+            format!(
+                "Could not create world generator `{}`",
+                world_info.generator
+            )
+        })?;
+    Ok((world_info.seed, generator))
+}
+
+/// Looks up a block that a generator needs.
+///
+/// # Errors
+/// If no block prototype has this name.
+pub fn required_block(
+    block_prototypes: &BlockPrototypes,
+    name: &str,
+) -> Result<&'static BlockPrototype> {
+    block_prototypes
+        .get(name)
+        .ok_or_else(|| anyhow!("World generation needs a block prototype named `{name}`."))
+}
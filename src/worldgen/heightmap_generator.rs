@@ -0,0 +1,375 @@
+//! Terrain read from a heightmap image, so that levels can be sketched in an image editor.
+//! The heightmap is a grayscale PNG or a 16-bit little endian RAW file. An optional colour image of the same size
+//! picks the surface block of each column, see [`HeightmapSettings::colour_blocks`].
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, anyhow, ensure};
+use bevy::{platform::collections::HashMap, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    chunky::chunk::{CHUNK_SIZE, CHUNK_SIZE_I32, CHUNK_SIZE2, ChunkData},
+    mod_manager::prototypes::BlockPrototype,
+    position::{ChunkPosition, Position},
+};
+
+use super::generator::{GeneratorContext, WorldGenerator, required_block};
+
+/// Chosen when the world is created and saved with it.
+/// The images are read again every time the world is opened, so editing them changes chunks that aren't generated yet.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HeightmapSettings {
+    /// a grayscale PNG, or a RAW file of 16-bit little endian heights if it ends in `.raw` or `.r16`
+    pub heightmap: PathBuf,
+    /// the width of a RAW heightmap in pixels. RAW heightmaps are assumed to be square if this is missing.
+    pub raw_width: Option<u32>,
+    /// an image the same size as the heightmap. The colour of each pixel picks the surface block of its column.
+    pub block_map: Option<PathBuf>,
+    /// `#rrggbb` colours of the block map, and the surface block they pick.
+    /// Colours that aren't listed use `surface_block`.
+    pub colour_blocks: BTreeMap<String, String>,
+    /// blocks per pixel
+    pub horizontal_scale: f32,
+    /// how many blocks the highest point of the heightmap is above the lowest
+    pub vertical_scale: f32,
+    /// the world position of the heightmap's first pixel at its lowest height
+    pub offset: [i32; 3],
+    /// fills everything outside the heightmap, up to the height of `offset`
+    pub outside_block: String,
+    pub surface_block: String,
+    pub subsurface_block: String,
+    pub filler_block: String,
+    /// how many blocks of `subsurface_block` are under the surface
+    pub subsurface_depth: u32,
+}
+
+impl Default for HeightmapSettings {
+    fn default() -> Self {
+        Self {
+            heightmap: PathBuf::from("heightmap.png"),
+            raw_width: None,
+            block_map: None,
+            colour_blocks: BTreeMap::new(),
+            horizontal_scale: 1.,
+            vertical_scale: 128.,
+            offset: [0, 128, 0],
+            outside_block: "air".to_string(),
+            surface_block: "grass".to_string(),
+            subsurface_block: "dirt".to_string(),
+            filler_block: "stone".to_string(),
+            subsurface_depth: 3,
+        }
+    }
+}
+
+/// Heights between 0 and 1, row by row.
+struct Heightmap {
+    width: usize,
+    depth: usize,
+    heights: Vec<f32>,
+}
+
+impl Heightmap {
+    fn load(path: &Path, raw_width: Option<u32>) -> Result<Self> {
+        let is_raw = path.extension().is_some_and(|extension| {
+            extension.eq_ignore_ascii_case("raw") || extension.eq_ignore_ascii_case("r16")
+        });
+        if !is_raw {
+            let image = image::open(path)
+                .with_context(|| format!("Could not read heightmap {}", path.display()))?
+                .into_luma16();
+            return Ok(Self {
+                width: image.width() as usize,
+                depth: image.height() as usize,
+                heights: image
+                    .pixels()
+                    .map(|pixel| f32::from(pixel.0[0]) / f32::from(u16::MAX))
+                    .collect(),
+            });
+        }
+
+        let bytes = fs::read(path)
+            .with_context(|| format!("Could not read heightmap {}", path.display()))?;
+        let heights: Vec<f32> = bytes
+            .chunks_exact(2)
+            .map(|pair| f32::from(u16::from_le_bytes([pair[0], pair[1]])) / f32::from(u16::MAX))
+            .collect();
+        let width = raw_width.map_or_else(
+            || (heights.len() as f64).sqrt() as usize,
+            |width| width as usize,
+        );
+        ensure!(
+            bytes.len() % 2 == 0 && width > 0 && !heights.is_empty() && heights.len() % width == 0,
+            "RAW heightmap {} is {} bytes, which isn't a whole number of 16-bit rows {width} pixels wide.",
+            path.display(),
+            bytes.len()
+        );
+        Ok(Self {
+            width,
+            depth: heights.len() / width,
+            heights,
+        })
+    }
+
+    /// The height between pixel centers, or `None` outside the heightmap.
+    fn sample(&self, pixel: Vec2) -> Option<f32> {
+        let max = Vec2::new((self.width - 1) as f32, (self.depth - 1) as f32);
+        if pixel.cmplt(Vec2::ZERO).any() || pixel.cmpgt(max).any() {
+            return None;
+        }
+        let corner = pixel.floor();
+        let t = pixel - corner;
+        let (x0, z0) = (corner.x as usize, corner.y as usize);
+        let (x1, z1) = ((x0 + 1).min(self.width - 1), (z0 + 1).min(self.depth - 1));
+        let at = |x: usize, z: usize| self.heights[x + z * self.width];
+
+        let lerp = |a: f32, b: f32, t: f32| (b - a).mul_add(t, a);
+        let near = lerp(at(x0, z0), at(x1, z0), t.x);
+        let far = lerp(at(x0, z1), at(x1, z1), t.x);
+        Some(lerp(near, far, t.y))
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Column {
+    Outside,
+    Inside {
+        height: f32,
+        surface: &'static BlockPrototype,
+    },
+}
+
+pub struct HeightmapWorldGenerator {
+    heightmap: Heightmap,
+    /// the surface block of each pixel, if there is a block map
+    surface_blocks: Option<Vec<&'static BlockPrototype>>,
+    horizontal_scale: f32,
+    vertical_scale: f32,
+    offset: IVec3,
+    subsurface_depth: f32,
+    air: &'static BlockPrototype,
+    outside: &'static BlockPrototype,
+    surface: &'static BlockPrototype,
+    subsurface: &'static BlockPrototype,
+    filler: &'static BlockPrototype,
+}
+
+impl HeightmapWorldGenerator {
+    pub const NAME: &'static str = "heightmap";
+
+    /// # Errors
+    /// If the world has no heightmap settings, an image can not be read,
+    /// the block map is a different size than the heightmap, or a block is missing.
+    pub fn new(context: &GeneratorContext) -> Result<Self> {
+        let settings = context
+            .heightmap
+            .ok_or_else(|| anyhow!("The heightmap generator needs heightmap settings."))?;
+        ensure!(
+            settings.horizontal_scale > 0.,
+            "The horizontal scale of a heightmap must be above 0."
+        );
+        let heightmap = Heightmap::load(&settings.heightmap, settings.raw_width)?;
+        let surface = required_block(context.block_prototypes, &settings.surface_block)?;
+
+        let surface_blocks = settings
+            .block_map
+            .as_deref()
+            .map(|path| -> Result<Vec<_>> {
+                let colours = settings
+                    .colour_blocks
+                    .iter()
+                    .map(|(colour, block)| {
+                        Ok((
+                            parse_colour(colour)?,
+                            required_block(context.block_prototypes, block)?,
+                        ))
+                    })
+                    .collect::<Result<HashMap<_, _>>>()?;
+                let image = image::open(path)
+                    .with_context(|| format!("Could not read block map {}", path.display()))?
+                    .into_rgb8();
+                ensure!(
+                    (image.width() as usize, image.height() as usize)
+                        == (heightmap.width, heightmap.depth),
+                    "Block map {} is {}x{}, but the heightmap is {}x{}.",
+                    path.display(),
+                    image.width(),
+                    image.height(),
+                    heightmap.width,
+                    heightmap.depth
+                );
+                Ok(image
+                    .pixels()
+                    .map(|pixel| colours.get(&pixel.0).copied().unwrap_or(surface))
+                    .collect())
+            })
+            .transpose()?;
+
+        Ok(Self {
+            heightmap,
+            surface_blocks,
+            horizontal_scale: settings.horizontal_scale,
+            vertical_scale: settings.vertical_scale,
+            offset: IVec3::from_array(settings.offset),
+            subsurface_depth: settings.subsurface_depth as f32,
+            air: required_block(context.block_prototypes, "air")?,
+            outside: required_block(context.block_prototypes, &settings.outside_block)?,
+            surface,
+            subsurface: required_block(context.block_prototypes, &settings.subsurface_block)?,
+            filler: required_block(context.block_prototypes, &settings.filler_block)?,
+        })
+    }
+
+    fn column(&self, x: i32, z: i32) -> Column {
+        let pixel =
+            IVec2::new(x - self.offset.x, z - self.offset.z).as_vec2() / self.horizontal_scale;
+        let Some(height) = self.heightmap.sample(pixel) else {
+            return Column::Outside;
+        };
+        let surface = self.surface_blocks.as_ref().map_or(self.surface, |blocks| {
+            let nearest = pixel.round();
+            blocks[nearest.x as usize + nearest.y as usize * self.heightmap.width]
+        });
+        Column::Inside {
+            height: height.mul_add(self.vertical_scale, self.offset.y as f32),
+            surface,
+        }
+    }
+}
+
+impl WorldGenerator for HeightmapWorldGenerator {
+    fn generate(&self, chunk_position: ChunkPosition) -> ChunkData {
+        let world_position = Position::from(chunk_position);
+        let columns: Vec<Column> = (0..CHUNK_SIZE2)
+            .map(|i| {
+                let x = (i % CHUNK_SIZE) as i32 + world_position.x;
+                let z = (i / CHUNK_SIZE) as i32 + world_position.z;
+                self.column(x, z)
+            })
+            .collect();
+
+        let bottom = world_position.y as f32;
+        let top = (world_position.y + CHUNK_SIZE_I32) as f32;
+        let outside_top = self.offset.y as f32;
+        if columns.iter().all(|column| match *column {
+            Column::Outside => bottom >= outside_top,
+            Column::Inside { height, .. } => bottom >= height,
+        }) {
+            return ChunkData::homogeneous(chunk_position, self.air);
+        }
+        if columns
+            .iter()
+            .all(|column| matches!(column, Column::Outside) && top <= outside_top)
+        {
+            return ChunkData::homogeneous(chunk_position, self.outside);
+        }
+        if columns.iter().all(|column| match *column {
+            Column::Outside => false,
+            Column::Inside { height, .. } => top <= height - 1. - self.subsurface_depth,
+        }) {
+            return ChunkData::homogeneous(chunk_position, self.filler);
+        }
+
+        ChunkData::from_fn(chunk_position, |local| {
+            let wy = (local.y + world_position.y) as f32;
+            match columns[local.x as usize + local.z as usize * CHUNK_SIZE] {
+                Column::Outside if wy < outside_top => self.outside,
+                Column::Outside => self.air,
+                Column::Inside { height, surface } => {
+                    let depth = height - wy;
+                    if depth <= 0. {
+                        self.air
+                    } else if depth < 1. {
+                        surface
+                    } else if depth < 1. + self.subsurface_depth {
+                        self.subsurface
+                    } else {
+                        self.filler
+                    }
+                }
+            }
+        })
+    }
+
+    fn surface_height(&self, x: i32, z: i32) -> Option<i32> {
+        match self.column(x, z) {
+            Column::Outside => self.outside.is_meshable.then_some(self.offset.y - 1),
+            Column::Inside { height, .. } => Some(height.ceil() as i32 - 1),
+        }
+    }
+}
+
+/// Parses a `#rrggbb` colour.
+fn parse_colour(colour: &str) -> Result<[u8; 3]> {
+    let hex = colour
+        .strip_prefix('#')
+        .filter(|hex| hex.len() == 6)
+        .ok_or_else(|| anyhow!("Expected a colour like `#40a020`, got `{colour}`."))?;
+    let value = u32::from_str_radix(hex, 16)
+        .with_context(|| format!("Expected a colour like `#40a020`, got `{colour}`."))?;
+    let [_, red, green, blue] = value.to_be_bytes();
+    Ok([red, green, blue])
+}
+
+#[test]
+fn raw_heightmap_shapes_terrain() {
+    use crate::{
+        mod_manager::prototypes::{
+            BiomePrototypesBuilder, BlockPrototypesBuilder, FeaturePrototypesBuilder,
+            FlatPresetPrototypesBuilder, PrototypesBuilder, RawBlockPrototype,
+            ResourcePrototypesBuilder,
+        },
+        worldgen::world_info::WorldSeed,
+    };
+
+    let path = std::env::temp_dir().join("talc_raw_heightmap_shapes_terrain.r16");
+    let heights: [u16; 4] = [0, u16::MAX, u16::MAX / 2, u16::MAX];
+    fs::write(&path, heights.map(u16::to_le_bytes).concat()).expect("Could not write heightmap");
+
+    let mut blocks = BlockPrototypesBuilder::new();
+    for (name, is_meshable) in [
+        ("air", false),
+        ("grass", true),
+        ("dirt", true),
+        ("stone", true),
+    ] {
+        blocks.add(RawBlockPrototype {
+            name: name.into(),
+            is_transparent: !is_meshable,
+            is_meshable,
+            color: Color::BLACK,
+        });
+    }
+    let settings = HeightmapSettings {
+        heightmap: path,
+        vertical_scale: 10.,
+        offset: [0, 0, 0],
+        ..default()
+    };
+    let generator = HeightmapWorldGenerator::new(&GeneratorContext {
+        block_prototypes: &blocks.build(),
+        biome_prototypes: &BiomePrototypesBuilder::new().build(),
+        resource_prototypes: &ResourcePrototypesBuilder::new().build(),
+        feature_prototypes: &FeaturePrototypesBuilder::new().build(),
+        flat_preset_prototypes: &FlatPresetPrototypesBuilder::new().build(),
+        caves: default(),
+        heightmap: Some(&settings),
+        flat: None,
+        seed: WorldSeed(0),
+    })
+    .expect("Could not create heightmap generator");
+    // the heightmap is read when the generator is created, so it isn't needed anymore
+    fs::remove_file(&settings.heightmap).expect("Could not remove heightmap");
+
+    assert_eq!(generator.surface_height(0, 0), Some(-1));
+    assert_eq!(generator.surface_height(1, 0), Some(9));
+    assert_eq!(generator.surface_height(0, 1), Some(4));
+    // outside is air
+    assert_eq!(generator.surface_height(5, 5), None);
+}
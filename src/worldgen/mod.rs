@@ -0,0 +1,10 @@
+pub mod autoplace;
+pub mod biome;
+pub mod caves;
+pub mod columns;
+pub mod features;
+pub mod generator;
+pub mod heightmap_generator;
+pub mod noise_generator;
+pub mod presets;
+pub mod world_info;
@@ -0,0 +1,346 @@
+//! The default terrain: rolling hills with overhangs, shaped by the biome of each column.
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use bevy::prelude::*;
+use bracket_noise::prelude::*;
+
+use crate::{
+    chunky::{
+        chunk::{CHUNK_SIZE_I32, ChunkData},
+        chunks_refs::ChunkRefs,
+    },
+    mod_manager::prototypes::{BiomePrototype, BlockPrototype},
+    position::{ChunkPosition, Position},
+};
+
+use super::{
+    autoplace::ResourcePlacer,
+    biome::BiomeMap,
+    caves::{CaveCarver, ChunkCaves},
+    columns::{ChunkColumn, ColumnCache, ColumnSample},
+    features::FeaturePlacer,
+    generator::{GeneratorContext, WorldGenerator, required_block},
+    world_info::WorldSeed,
+};
+
+/// How quickly the terrain rises and falls. Lower gives wider hills.
+const HEIGHT_FREQUENCY: f32 = 0.002_591;
+/// How quickly the sideways push of overhangs changes.
+const OVERHANG_FREQUENCY: f32 = 0.0254;
+
+const HEIGHT_SALT: u64 = 0;
+const OVERHANG_SALT: u64 = 1;
+
+pub struct NoiseWorldGenerator {
+    seed: WorldSeed,
+    air: &'static BlockPrototype,
+    biomes: BiomeMap,
+    resources: ResourcePlacer,
+    caves: CaveCarver,
+    features: FeaturePlacer,
+    columns: ColumnCache,
+    /// Chunks starting above this height are always empty.
+    sky_height: i32,
+}
+
+impl NoiseWorldGenerator {
+    pub const NAME: &'static str = "noise";
+
+    /// # Errors
+    /// If the `air` block prototype is missing, there are no biomes,
+    /// or a biome, resource or feature uses a missing block or biome.
+    pub fn new(context: &GeneratorContext) -> Result<Self> {
+        let biomes = BiomeMap::new(
+            context.biome_prototypes,
+            context.block_prototypes,
+            context.seed,
+        )?;
+        Ok(Self {
+            seed: context.seed,
+            air: required_block(context.block_prototypes, "air")?,
+            sky_height: biomes.max_height().ceil() as i32,
+            biomes,
+            resources: ResourcePlacer::new(
+                context.resource_prototypes,
+                context.block_prototypes,
+                context.seed,
+            )?,
+            caves: CaveCarver::new(context.caves, context.seed),
+            features: FeaturePlacer::new(
+                context.feature_prototypes,
+                context.block_prototypes,
+                context.biome_prototypes,
+                context.seed,
+            )?,
+            columns: ColumnCache::default(),
+        })
+    }
+
+    /// The biome and shape of every block column in a column of chunks, at chunk x and z.
+    fn column(&self, column_position: IVec2) -> Arc<ChunkColumn> {
+        self.columns.get_or_insert_with(column_position, || {
+            let min = column_position * CHUNK_SIZE_I32;
+            let biome_sampler = self.biomes.sampler();
+            ChunkColumn::from_fn(|x, z| {
+                let sample = biome_sampler.sample((min.x + x) as f32, (min.y + z) as f32);
+                ColumnSample {
+                    biome: sample.biome,
+                    shape: sample.shape,
+                }
+            })
+        })
+    }
+
+    fn noise(&self, salt: u64, frequency: f32) -> FastNoise {
+        let mut noise = FastNoise::seeded(self.seed.derive(salt));
+        noise.set_frequency(frequency);
+        noise
+    }
+
+    /// How far below the surface a block is, before caves. Positive is solid.
+    /// Overhangs push the height noise sideways by a different amount at every height.
+    fn depth(
+        column: &ColumnSample,
+        height_noise: &FastNoise,
+        overhang_noise: &FastNoise,
+        position: Vec3,
+    ) -> f32 {
+        let overhang =
+            overhang_noise.get_noise3d(position.x, position.y - column.shape.height, position.z)
+                * column.shape.overhang;
+        let surface = height_noise
+            .get_noise(position.x + overhang, position.z / 3.0)
+            .mul_add(column.shape.height_variation, column.shape.height);
+        surface - position.y
+    }
+}
+
+impl WorldGenerator for NoiseWorldGenerator {
+    /// use noise shape our voxel data based on the `chunk_pos`
+    fn generate(&self, chunk_position: ChunkPosition) -> ChunkData {
+        let world_position = Position::from(chunk_position);
+        if world_position.y > self.sky_height {
+            return ChunkData::homogeneous(chunk_position, self.air);
+        }
+
+        let column = self.column(IVec2::new(chunk_position.x, chunk_position.z));
+        if world_position.y as f32 > column.max_surface() {
+            return ChunkData::homogeneous(chunk_position, self.air);
+        }
+
+        let caves = self.caves.carve_chunk(chunk_position);
+        if matches!(caves, ChunkCaves::Carved) {
+            return ChunkData::homogeneous(chunk_position, self.air);
+        }
+
+        let patches = self.resources.patches_in_chunk(chunk_position);
+
+        // deep enough below every column's surface, all that's left is filler
+        let chunk_top = (world_position.y + CHUNK_SIZE_I32) as f32;
+        if let Some(filler) = column.filler_below(chunk_top)
+            && patches.is_empty()
+            && matches!(caves, ChunkCaves::Solid)
+        {
+            return ChunkData::homogeneous(chunk_position, filler);
+        }
+
+        let height_noise = self.noise(HEIGHT_SALT, HEIGHT_FREQUENCY);
+        let overhang_noise = self.noise(OVERHANG_SALT, OVERHANG_FREQUENCY);
+
+        ChunkData::from_fn(chunk_position, |local| {
+            let sample = column.get(local.x, local.z);
+            let position = (world_position.0 + local.0).as_vec3();
+            let depth = Self::depth(sample, &height_noise, &overhang_noise, position);
+
+            if depth <= 0. || caves.is_carved(local) {
+                self.air
+            } else if depth < 1. {
+                sample.biome.surface
+            } else {
+                // ores never poke out of the surface
+                patches
+                    .ore_at(world_position.0 + local.0)
+                    .unwrap_or_else(|| sample.biome.block_at_depth(depth))
+            }
+        })
+    }
+
+    fn decorate(&self, terrain: &ChunkRefs) -> Option<ChunkData> {
+        self.features
+            .decorate(terrain, &self.biomes.sampler(), &|x, z| {
+                self.surface_height(x, z)
+            })
+    }
+
+    /// Caves are ignored, so this is the surface as seen from the sky.
+    fn surface_height(&self, x: i32, z: i32) -> Option<i32> {
+        let position = Position::new(x, 0, z);
+        let chunk = ChunkPosition::from(position);
+        let local = position.local_to_chunk();
+        let column = self.column(IVec2::new(chunk.x, chunk.z));
+        let sample = column.get(local.x, local.z);
+        let height_noise = self.noise(HEIGHT_SALT, HEIGHT_FREQUENCY);
+        let overhang_noise = self.noise(OVERHANG_SALT, OVERHANG_FREQUENCY);
+
+        // overhangs only push the height noise sideways, so the surface stays within this range
+        let top = sample.max_surface().ceil() as i32;
+        let bottom = sample.min_surface().floor() as i32;
+        (bottom..=top)
+            .rev()
+            .find(|&y| {
+                let position = IVec3::new(x, y, z).as_vec3();
+                Self::depth(sample, &height_noise, &overhang_noise, position) > 0.
+            })
+            .or(Some(bottom - 1))
+    }
+
+    fn biome_at(&self, x: i32, z: i32) -> Option<&'static BiomePrototype> {
+        let position = Position::new(x, 0, z);
+        let chunk = ChunkPosition::from(position);
+        let local = position.local_to_chunk();
+        let column = self.column(IVec2::new(chunk.x, chunk.z));
+        Some(column.get(local.x, local.z).biome.prototype)
+    }
+}
+
+#[test]
+fn generation_is_deterministic() {
+    use crate::mod_manager::prototypes::{
+        BiomePrototypesBuilder, BlockPrototypesBuilder, FeaturePrototypesBuilder,
+        FlatPresetPrototypesBuilder, PrototypesBuilder, RawBiomePrototype, RawBlockPrototype,
+        ResourcePrototypesBuilder,
+    };
+
+    let mut blocks = BlockPrototypesBuilder::new();
+    for (name, is_meshable) in [("air", false), ("grass", true), ("stone", true)] {
+        blocks.add(RawBlockPrototype {
+            name: name.into(),
+            is_transparent: !is_meshable,
+            is_meshable,
+            color: Color::BLACK,
+        });
+    }
+    let blocks = blocks.build();
+    let mut biomes = BiomePrototypesBuilder::new();
+    biomes.add(RawBiomePrototype {
+        name: "hills".into(),
+        temperature: 0.,
+        humidity: 0.,
+        surface_block: "grass".into(),
+        subsurface_block: "stone".into(),
+        filler_block: "stone".into(),
+        subsurface_depth: 3,
+        height: 200.,
+        height_variation: 30.,
+        overhang: 55.,
+    });
+    let biomes = biomes.build();
+    let resources = ResourcePrototypesBuilder::new().build();
+    let features = FeaturePrototypesBuilder::new().build();
+    let flat_presets = FlatPresetPrototypesBuilder::new().build();
+    let generator_with_seed = |seed| {
+        NoiseWorldGenerator::new(&GeneratorContext {
+            block_prototypes: &blocks,
+            biome_prototypes: &biomes,
+            resource_prototypes: &resources,
+            feature_prototypes: &features,
+            flat_preset_prototypes: &flat_presets,
+            caves: default(),
+            heightmap: None,
+            flat: None,
+            seed: WorldSeed(seed),
+        })
+        .expect("Missing prototypes")
+    };
+
+    let positions = [
+        ChunkPosition::new(0, 6, 0),
+        ChunkPosition::new(-3, 5, 7),
+        ChunkPosition::new(12, 7, -40),
+    ];
+    let generator = generator_with_seed(1234);
+    let same_seed = generator_with_seed(1234);
+    let other_seed = generator_with_seed(4321);
+    for position in positions {
+        assert_eq!(generator.generate(position), generator.generate(position));
+        assert_eq!(generator.generate(position), same_seed.generate(position));
+    }
+    assert!(
+        positions
+            .iter()
+            .any(|&position| generator.generate(position) != other_seed.generate(position))
+    );
+}
+
+#[test]
+fn surface_height_matches_generated_terrain() {
+    use crate::{
+        mod_manager::prototypes::{
+            BiomePrototypesBuilder, BlockPrototypesBuilder, FeaturePrototypesBuilder,
+            FlatPresetPrototypesBuilder, Prototypes, PrototypesBuilder, RawBiomePrototype,
+            RawBlockPrototype, ResourcePrototypesBuilder,
+        },
+        worldgen::caves::CaveSettings,
+    };
+
+    let mut blocks = BlockPrototypesBuilder::new();
+    for (name, is_meshable) in [("air", false), ("grass", true), ("stone", true)] {
+        blocks.add(RawBlockPrototype {
+            name: name.into(),
+            is_transparent: !is_meshable,
+            is_meshable,
+            color: Color::BLACK,
+        });
+    }
+    let blocks = blocks.build();
+    let mut biomes = BiomePrototypesBuilder::new();
+    biomes.add(RawBiomePrototype {
+        name: "hills".into(),
+        temperature: 0.,
+        humidity: 0.,
+        surface_block: "grass".into(),
+        subsurface_block: "stone".into(),
+        filler_block: "stone".into(),
+        subsurface_depth: 3,
+        height: 200.,
+        height_variation: 30.,
+        overhang: 55.,
+    });
+    let biomes = biomes.build();
+    let generator = NoiseWorldGenerator::new(&GeneratorContext {
+        block_prototypes: &blocks,
+        biome_prototypes: &biomes,
+        resource_prototypes: &ResourcePrototypesBuilder::new().build(),
+        feature_prototypes: &FeaturePrototypesBuilder::new().build(),
+        flat_preset_prototypes: &FlatPresetPrototypesBuilder::new().build(),
+        caves: CaveSettings {
+            density: 0.,
+            ..default()
+        },
+        heightmap: None,
+        flat: None,
+        seed: WorldSeed(1234),
+    })
+    .expect("Missing prototypes");
+    // the global block registry may only be set once, so blocks are looked up by id here
+    let is_meshable = |id| {
+        blocks
+            .iter()
+            .any(|(_, block)| block.id == id && block.is_meshable)
+    };
+
+    for (x, z) in [(0, 0), (-17, 40), (300, -5)] {
+        let surface = generator
+            .surface_height(x, z)
+            .expect("Noise terrain always has a surface");
+        let block_at = |y| {
+            let position = Position::new(x, y, z);
+            let chunk = generator.generate(ChunkPosition::from(position));
+            chunk.get_block_id(position.local_to_chunk().into())
+        };
+        assert!(is_meshable(block_at(surface)));
+        assert!(!is_meshable(block_at(surface + 1)));
+    }
+}
@@ -0,0 +1,272 @@
+//! Simple worlds for testing: flat layers, an empty void, and a grid of every block.
+
+use anyhow::{Result, anyhow, ensure};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    chunky::chunk::{CHUNK_SIZE_I32, ChunkData},
+    mod_manager::prototypes::{BlockPrototype, FlatLayer, Prototypes},
+    position::{ChunkPosition, Position},
+};
+
+use super::generator::{GeneratorContext, WorldGenerator, required_block};
+
+/// Chosen when a flat world is created and saved with it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FlatSettings {
+    /// the name of a flat preset prototype. Ignored if `layers` isn't empty.
+    pub preset: String,
+    /// from the bottom up
+    pub layers: Vec<FlatLayer>,
+    /// the y of the bottom of the lowest layer. Everything below it is empty.
+    pub bottom: i32,
+}
+
+impl Default for FlatSettings {
+    fn default() -> Self {
+        Self {
+            preset: "default".to_string(),
+            layers: vec![],
+            bottom: 0,
+        }
+    }
+}
+
+/// The same layers of blocks everywhere.
+pub struct FlatWorldGenerator {
+    air: &'static BlockPrototype,
+    /// the block at each height, from `bottom` up
+    blocks: Vec<&'static BlockPrototype>,
+    bottom: i32,
+}
+
+impl FlatWorldGenerator {
+    pub const NAME: &'static str = "flat";
+
+    /// # Errors
+    /// If the preset doesn't exist, or a layer uses a missing block.
+    pub fn new(context: &GeneratorContext) -> Result<Self> {
+        let default = FlatSettings::default();
+        let settings = context.flat.unwrap_or(&default);
+        let layers = if settings.layers.is_empty() {
+            &context
+                .flat_preset_prototypes
+                .get(&settings.preset)
+                .ok_or_else(|| anyhow!("Unknown flat preset `{}`.", settings.preset))?
+                .layers
+        } else {
+            &settings.layers
+        };
+
+        let mut blocks = vec![];
+        for layer in layers {
+            let block = required_block(context.block_prototypes, &layer.block)?;
+            blocks.extend(std::iter::repeat_n(block, layer.thickness as usize));
+        }
+        Ok(Self {
+            air: required_block(context.block_prototypes, "air")?,
+            blocks,
+            bottom: settings.bottom,
+        })
+    }
+
+    fn block_at(&self, y: i32) -> &'static BlockPrototype {
+        usize::try_from(y - self.bottom)
+            .ok()
+            .and_then(|index| self.blocks.get(index))
+            .copied()
+            .unwrap_or(self.air)
+    }
+}
+
+impl WorldGenerator for FlatWorldGenerator {
+    fn generate(&self, chunk_position: ChunkPosition) -> ChunkData {
+        let min_y = Position::from(chunk_position).y;
+        let first = self.block_at(min_y);
+        if (min_y..min_y + CHUNK_SIZE_I32).all(|y| self.block_at(y) == first) {
+            return ChunkData::homogeneous(chunk_position, first);
+        }
+        ChunkData::from_fn(chunk_position, |local| self.block_at(min_y + local.y))
+    }
+
+    fn surface_height(&self, _x: i32, _z: i32) -> Option<i32> {
+        self.blocks
+            .iter()
+            .rposition(|block| block.is_meshable)
+            .map(|index| self.bottom + index as i32)
+    }
+}
+
+/// Nothing but air.
+pub struct VoidWorldGenerator {
+    air: &'static BlockPrototype,
+}
+
+impl VoidWorldGenerator {
+    pub const NAME: &'static str = "void";
+
+    /// # Errors
+    /// If the `air` block prototype is missing.
+    pub fn new(context: &GeneratorContext) -> Result<Self> {
+        Ok(Self {
+            air: required_block(context.block_prototypes, "air")?,
+        })
+    }
+}
+
+impl WorldGenerator for VoidWorldGenerator {
+    fn generate(&self, chunk_position: ChunkPosition) -> ChunkData {
+        ChunkData::homogeneous(chunk_position, self.air)
+    }
+}
+
+/// The height the debug grid is laid out at.
+const DEBUG_GRID_HEIGHT: i32 = 64;
+/// Blocks between the blocks of the debug grid, so that every face can be seen.
+const DEBUG_GRID_SPACING: i32 = 2;
+
+/// Every block laid out in a square grid in the sky, starting at x = 0, z = 0, to check how they render.
+/// Blocks have no states, so each appears once. Blocks that aren't meshed are left out.
+pub struct DebugWorldGenerator {
+    air: &'static BlockPrototype,
+    /// in the order they were registered
+    blocks: Vec<&'static BlockPrototype>,
+    /// how many blocks are in each row
+    row_length: i32,
+}
+
+impl DebugWorldGenerator {
+    pub const NAME: &'static str = "debug";
+
+    /// # Errors
+    /// If the `air` block prototype is missing, or there are no blocks to show.
+    pub fn new(context: &GeneratorContext) -> Result<Self> {
+        let mut blocks: Vec<_> = context
+            .block_prototypes
+            .iter()
+            .map(|(_, &block)| block)
+            .filter(|block| block.is_meshable)
+            .collect();
+        blocks.sort_by_key(|block| block.id);
+        ensure!(!blocks.is_empty(), "There are no blocks to show.");
+        Ok(Self {
+            air: required_block(context.block_prototypes, "air")?,
+            row_length: (blocks.len() as f64).sqrt().ceil() as i32,
+            blocks,
+        })
+    }
+
+    fn block_at(&self, x: i32, z: i32) -> Option<&'static BlockPrototype> {
+        if x < 0 || z < 0 || x % DEBUG_GRID_SPACING != 0 || z % DEBUG_GRID_SPACING != 0 {
+            return None;
+        }
+        let (column, row) = (x / DEBUG_GRID_SPACING, z / DEBUG_GRID_SPACING);
+        if column >= self.row_length {
+            return None;
+        }
+        self.blocks
+            .get((row * self.row_length + column) as usize)
+            .copied()
+    }
+}
+
+impl WorldGenerator for DebugWorldGenerator {
+    fn generate(&self, chunk_position: ChunkPosition) -> ChunkData {
+        let min = Position::from(chunk_position);
+        if !(min.y..min.y + CHUNK_SIZE_I32).contains(&DEBUG_GRID_HEIGHT) {
+            return ChunkData::homogeneous(chunk_position, self.air);
+        }
+        ChunkData::from_fn(chunk_position, |local| {
+            if min.y + local.y == DEBUG_GRID_HEIGHT {
+                self.block_at(min.x + local.x, min.z + local.z)
+                    .unwrap_or(self.air)
+            } else {
+                self.air
+            }
+        })
+    }
+
+    fn surface_height(&self, x: i32, z: i32) -> Option<i32> {
+        self.block_at(x, z).map(|_| DEBUG_GRID_HEIGHT)
+    }
+}
+
+#[test]
+fn flat_surface_height_matches_generated_terrain() {
+    use bevy::color::Color;
+
+    use crate::{
+        mod_manager::prototypes::{
+            BiomePrototypesBuilder, BlockPrototypesBuilder, FeaturePrototypesBuilder,
+            FlatPresetPrototypesBuilder, PrototypesBuilder, RawBlockPrototype,
+            RawFlatPresetPrototype, ResourcePrototypesBuilder,
+        },
+        worldgen::{caves::CaveSettings, world_info::WorldSeed},
+    };
+
+    let mut blocks = BlockPrototypesBuilder::new();
+    for (name, is_meshable) in [
+        ("air", false),
+        ("grass", true),
+        ("dirt", true),
+        ("stone", true),
+    ] {
+        blocks.add(RawBlockPrototype {
+            name: name.into(),
+            is_transparent: !is_meshable,
+            is_meshable,
+            color: Color::BLACK,
+        });
+    }
+    let blocks = blocks.build();
+    let layer = |block: &str, thickness| FlatLayer {
+        block: block.into(),
+        thickness,
+    };
+    let mut presets = FlatPresetPrototypesBuilder::new();
+    presets.add(RawFlatPresetPrototype {
+        name: "layered".into(),
+        layers: vec![
+            layer("stone", 3),
+            layer("dirt", 2),
+            layer("grass", 1),
+            // air on top isn't part of the surface
+            layer("air", 2),
+        ],
+    });
+    // the layers cross the border between the chunks at y -1 and y 0
+    let settings = FlatSettings {
+        preset: "layered".to_string(),
+        layers: vec![],
+        bottom: -4,
+    };
+    let generator = FlatWorldGenerator::new(&GeneratorContext {
+        block_prototypes: &blocks,
+        biome_prototypes: &BiomePrototypesBuilder::new().build(),
+        resource_prototypes: &ResourcePrototypesBuilder::new().build(),
+        feature_prototypes: &FeaturePrototypesBuilder::new().build(),
+        flat_preset_prototypes: &presets.build(),
+        caves: CaveSettings::default(),
+        heightmap: None,
+        flat: Some(&settings),
+        seed: WorldSeed(0),
+    })
+    .expect("Missing prototypes");
+    // the global block registry may only be set once, so blocks are looked up by id here
+    let is_meshable = |id| {
+        blocks
+            .iter()
+            .any(|(_, block)| block.id == id && block.is_meshable)
+    };
+
+    for (x, z) in [(0, 0), (-17, 40), (300, -5)] {
+        let highest_solid = (-40..40).rev().find(|&y| {
+            let position = Position::new(x, y, z);
+            let chunk = generator.generate(ChunkPosition::from(position));
+            is_meshable(chunk.get_block_id(position.local_to_chunk().into()))
+        });
+        assert_eq!(generator.surface_height(x, z), highest_solid);
+        assert_eq!(highest_solid, Some(1));
+    }
+}
@@ -0,0 +1,136 @@
+//! What a world was created with. Saved to `world.toml` in the world's directory,
+//! so that reopening a world generates the same terrain.
+
+use std::{fs, path::Path};
+
+use anyhow::{Context, Result};
+use bevy::prelude::*;
+use rand::{SeedableRng, rngs::StdRng};
+use serde::{Deserialize, Serialize};
+
+use crate::position::ChunkPosition;
+
+use super::{caves::CaveSettings, heightmap_generator::HeightmapSettings, presets::FlatSettings};
+
+/// Seeds every noise function and random number generator used by world generation.
+// toml integers are signed, so the seed is saved as the i64 with the same bits.
+#[derive(Resource, Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(from = "i64", into = "i64")]
+pub struct WorldSeed(pub u64);
+
+impl From<i64> for WorldSeed {
+    fn from(value: i64) -> Self {
+        Self(value.cast_unsigned())
+    }
+}
+
+impl From<WorldSeed> for i64 {
+    fn from(value: WorldSeed) -> Self {
+        value.0.cast_signed()
+    }
+}
+
+impl WorldSeed {
+    /// A seed for a single noise layer or feature, so that layers don't repeat each other's patterns.
+    #[must_use]
+    pub const fn derive(self, salt: u64) -> u64 {
+        splitmix64(self.0 ^ splitmix64(salt))
+    }
+
+    /// A random number generator that always produces the same numbers for the same seed, chunk and salt,
+    /// regardless of the order chunks are generated in.
+    #[must_use]
+    pub fn chunk_rng(self, chunk_position: ChunkPosition, salt: u64) -> StdRng {
+        self.cell_rng(chunk_position.0, salt)
+    }
+
+    /// Like [`WorldSeed::chunk_rng`], for grids of any size.
+    #[must_use]
+    pub fn cell_rng(self, cell: IVec3, salt: u64) -> StdRng {
+        StdRng::seed_from_u64(self.hash_position(cell, salt))
+    }
+
+    /// Hashes any grid position, such as a block or a cell of a larger grid.
+    #[must_use]
+    pub const fn hash_position(self, position: IVec3, salt: u64) -> u64 {
+        let hash = splitmix64(
+            splitmix64(splitmix64(position.x as u64) ^ position.y as u64) ^ position.z as u64,
+        );
+        self.derive(salt) ^ hash
+    }
+}
+
+/// A salt for something that is identified by name, such as a prototype.
+/// Unlike an index it doesn't change when mods add or remove other prototypes.
+#[must_use]
+pub const fn name_salt(name: &str) -> u64 {
+    // FNV-1a
+    let bytes = name.as_bytes();
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let mut i = 0;
+    while i < bytes.len() {
+        hash ^= bytes[i] as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+        i += 1;
+    }
+    hash
+}
+
+/// A fast, well mixed hash of a single integer.
+const fn splitmix64(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WorldInfo {
+    pub generator: String,
+    pub seed: WorldSeed,
+    /// missing from worlds created before caves existed
+    #[serde(default)]
+    pub caves: CaveSettings,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub heightmap: Option<HeightmapSettings>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub flat: Option<FlatSettings>,
+}
+
+impl WorldInfo {
+    pub const FILE_NAME: &'static str = "world.toml";
+
+    /// Reads the world in `directory`.
+    ///
+    /// # Errors
+    /// If there is no world in `directory`, or its file can not be read.
+    pub fn load(directory: &Path) -> Result<Self> {
+        let path = directory.join(Self::FILE_NAME);
+        let contents = fs::read_to_string(&path)
+            .with_context(|| format!("Could not read {}", path.display()))?;
+        toml::from_str(&contents).with_context(|| format!("Could not parse {}", path.display()))
+    }
+
+    /// Reads the world in `directory`, or creates it with `new` if there is none yet.
+    ///
+    /// # Errors
+    /// If the existing file can not be read, or the new one can not be written.
+    pub fn load_or_create(directory: &Path, new: impl FnOnce() -> Self) -> Result<Self> {
+        let path = directory.join(Self::FILE_NAME);
+        if path.is_file() {
+            return Self::load(directory);
+        }
+
+        let world_info = new();
+        fs::create_dir_all(directory)
+            .with_context(|| format!("Could not create {}", directory.display()))?;
+        fs::write(&path, toml::to_string(&world_info)?)
+            .with_context(|| format!("Could not write {}", path.display()))?;
+        info!(
+            "Created world in {} with seed {}",
+            directory.display(),
+            world_info.seed.0
+        );
+        Ok(world_info)
+    }
+}